@@ -0,0 +1,205 @@
+use std::{
+    env,
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Directory scanned for CHIP-8 games, see `roms/README.md`.
+const ROM_DIR: &str = "roms";
+
+const QUIRKS: [&str; 5] = ["vf_reset", "shift", "load_store", "jump", "clip"];
+
+/// `variant` values and the `chip8::Variant` each selects.
+const VARIANTS: [(&str, &str); 3] = [
+    ("chip8", "Chip8"),
+    ("schip", "SuperChip"),
+    ("xochip", "XoChip"),
+];
+
+fn main() {
+    // defmt needs its own linker script next to cortex-m-rt's `link.x`.
+    if env::var_os("CARGO_FEATURE_DEFMT").is_some() {
+        println!("cargo:rustc-link-arg-bins=-Tdefmt.x");
+    }
+
+    rom_catalog();
+}
+
+struct Game {
+    title: String,
+    rom: PathBuf,
+    keymap: [Option<u8>; 12],
+    shift_keymap: [Option<u8>; 12],
+    speed: u32,
+    variant: &'static str,
+    quirks: Vec<String>,
+    tone: u32,
+}
+
+/// Reads a `.meta` file. The ROM defaults to the `.ch8` file of the same name.
+fn parse_meta(path: &Path) -> Game {
+    let text = fs::read_to_string(path).unwrap();
+    let mut game = Game {
+        title: path.file_stem().unwrap().to_string_lossy().into_owned(),
+        rom: path.with_extension("ch8"),
+        // `Keymap::DEFAULT` in `src/chip8.rs`.
+        keymap: [1, 2, 3, 4, 5, 6, 7, 8, 9, 0xa, 0, 0xb].map(Some),
+        shift_keymap: [
+            None,
+            None,
+            Some(0xc),
+            None,
+            None,
+            Some(0xd),
+            None,
+            None,
+            Some(0xe),
+            None,
+            None,
+            Some(0xf),
+        ],
+        speed: 10,
+        variant: "Chip8",
+        quirks: Vec::new(),
+        tone: 440,
+    };
+
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let error = |message: &str| -> ! {
+            panic!("{}:{}: {}", path.display(), number + 1, message);
+        };
+        let Some((key, value)) = line.split_once('=') else {
+            error("expected `key = value`");
+        };
+
+        match (key.trim(), value.trim()) {
+            ("title", title) => game.title = title.to_owned(),
+            ("file", file) => game.rom = path.parent().unwrap().join(file),
+            ("keymap", keys) => game.keymap = parse_keymap(keys).unwrap_or_else(|e| error(e)),
+            ("shift_keymap", keys) => {
+                game.shift_keymap = parse_keymap(keys).unwrap_or_else(|e| error(e));
+            }
+            ("speed", speed) => {
+                game.speed = speed
+                    .parse()
+                    .ok()
+                    .filter(|speed| (1..=100).contains(speed))
+                    .unwrap_or_else(|| error("the speed is 1 to 100 instructions per frame"));
+            }
+            ("variant", variant) => {
+                game.variant = VARIANTS
+                    .iter()
+                    .find(|(name, _)| *name == variant)
+                    .map(|(_, variant)| *variant)
+                    .unwrap_or_else(|| {
+                        let names: Vec<&str> = VARIANTS.iter().map(|(name, _)| *name).collect();
+                        error(&format!("unknown variant, expected one of {:?}", names))
+                    });
+            }
+            ("tone", tone) => {
+                game.tone = tone
+                    .parse()
+                    .unwrap_or_else(|_| error("the tone is a frequency in Hz"));
+            }
+            ("quirks", quirks) => {
+                game.quirks = quirks
+                    .split(|c: char| c == ',' || c.is_whitespace())
+                    .filter(|quirk| !quirk.is_empty())
+                    .map(|quirk| {
+                        if !QUIRKS.contains(&quirk) {
+                            error(&format!("unknown quirk, expected one of {:?}", QUIRKS));
+                        }
+                        quirk.to_owned()
+                    })
+                    .collect();
+            }
+            (key, _) => error(&format!("unknown key `{}`", key)),
+        }
+    }
+
+    game
+}
+
+/// A keymap is twelve hex digits, `-` for unmapped keys.
+fn parse_keymap(keys: &str) -> Result<[Option<u8>; 12], &'static str> {
+    let keys = keys
+        .split_whitespace()
+        .map(|key| match key {
+            "-" => Ok(None),
+            key => u8::from_str_radix(key, 16)
+                .ok()
+                .filter(|key| *key < 16)
+                .map(Some)
+                .ok_or("keys are hex digits 0 to F, or - if unmapped"),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    keys.try_into()
+        .map_err(|_| "a keymap needs one entry per key, 12")
+}
+
+/// Generates `ROMS` and `ROM_MENU` for `src/chip8.rs` from the games in
+/// `ROM_DIR`.
+fn rom_catalog() {
+    let dir = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join(ROM_DIR);
+    println!("cargo:rerun-if-changed={}", dir.display());
+
+    let mut metas: Vec<PathBuf> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "meta")
+        })
+        .collect();
+    metas.sort();
+
+    let games: Vec<Game> = metas.iter().map(|meta| parse_meta(meta)).collect();
+
+    let mut code = String::new();
+    writeln!(code, "const ROMS: [Rom; {}] = [", games.len()).unwrap();
+    for (meta, game) in metas.iter().zip(&games) {
+        println!("cargo:rerun-if-changed={}", meta.display());
+        println!("cargo:rerun-if-changed={}", game.rom.display());
+        let rom = game
+            .rom
+            .canonicalize()
+            .unwrap_or_else(|_| panic!("{}: ROM {} not found", meta.display(), game.rom.display()));
+
+        let quirks: String = QUIRKS
+            .iter()
+            .map(|quirk| format!("{}: {}, ", quirk, game.quirks.iter().any(|q| q == quirk)))
+            .collect();
+        writeln!(
+            code,
+            "    Rom {{ name: {:?}, data: include_bytes!({:?}), keymap: Keymap {{ keys: {:?}, shifted: {:?} }}, speed: {}, variant: Variant::{}, quirks: Quirks {{ {}}}, tone: {}, slot: None }},",
+            game.title, rom, game.keymap, game.shift_keymap, game.speed, game.variant, quirks, game.tone
+        )
+        .unwrap();
+    }
+    writeln!(code, "];").unwrap();
+
+    writeln!(code, "const ROM_MENU: [MenuItem; {}] = [", games.len()).unwrap();
+    for (index, game) in games.iter().enumerate() {
+        // Only the first twelve games get a key of their own.
+        if index < 12 {
+            writeln!(
+                code,
+                "    MenuItem::new({:?}).with_key({}),",
+                game.title, index
+            )
+            .unwrap();
+        } else {
+            writeln!(code, "    MenuItem::new({:?}),", game.title).unwrap();
+        }
+    }
+    writeln!(code, "];").unwrap();
+
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("roms.rs");
+    fs::write(out, code).unwrap();
+}
@@ -0,0 +1,147 @@
+//! Renders menus into an in-memory frame and compares them with the golden
+//! images in `goldens/`.
+//!
+//! ```text
+//! cargo goldens                # check, writes diffs to target/goldens
+//! cargo goldens --regenerate   # overwrite the goldens with the current output
+//! ```
+//!
+//! A golden is the ASCII dump from `hal::host::to_ascii`, so changes show up in
+//! a normal diff. For every mismatch the actual frame and a frame with just the
+//! differing pixels lit are written as PNGs.
+
+use std::{env, fs, path::Path, process::ExitCode};
+
+use embedded_graphics::{draw_target::DrawTargetExt, prelude::OriginDimensions, Drawable};
+use macropad_apps::{
+    app,
+    framebuffer::FrameBuffer,
+    hal::host::{from_ascii, to_ascii, to_png},
+    icons,
+    menu::{Menu, MenuItem, MenuStyle},
+    status_bar,
+};
+
+const ITEMS: [MenuItem; 4] = [
+    MenuItem::new("Chip-8 Emulator"),
+    MenuItem::new("Set Date & Time"),
+    MenuItem::new("Settings"),
+    MenuItem::new("About"),
+];
+
+const LONG_ITEMS: [MenuItem; 10] = [
+    MenuItem::new("One"),
+    MenuItem::new("Two"),
+    MenuItem::new("Three"),
+    MenuItem::new("Four"),
+    MenuItem::new("Five"),
+    MenuItem::new("Six"),
+    MenuItem::new("Seven"),
+    MenuItem::new("Eight"),
+    MenuItem::new("Nine"),
+    MenuItem::new("Ten"),
+];
+
+const DECORATED_ITEMS: [MenuItem; 3] = [
+    MenuItem::new("Chip-8 Emulator")
+        .with_icon(&icons::GAMEPAD)
+        .with_key(0),
+    MenuItem::new("Set Date & Time")
+        .with_icon(&icons::CLOCK)
+        .with_key(1),
+    MenuItem::new("No icon").with_key(11),
+];
+
+fn menu(items: &'static [MenuItem<'static>], selected: usize) -> FrameBuffer {
+    let mut frame = FrameBuffer::new();
+    let mut menu = Menu::with_style(items, frame.size().height, MenuStyle::default());
+    menu.select_item(selected);
+    menu.draw(&mut frame).unwrap();
+
+    frame
+}
+
+fn main_menu() -> FrameBuffer {
+    let mut frame = FrameBuffer::new();
+    status_bar::set_enabled(true);
+    status_bar::set_mode("Menu");
+    let content_area = status_bar::content_area(frame.size());
+
+    let menu = Menu::with_style(&app::MENU, content_area.size.height, MenuStyle::default());
+    status_bar::draw(&mut frame).unwrap();
+    menu.draw(&mut frame.cropped(&content_area)).unwrap();
+
+    frame
+}
+
+fn scenes() -> Vec<(&'static str, FrameBuffer)> {
+    vec![
+        ("menu_first_selected", menu(&ITEMS, 0)),
+        ("menu_last_selected", menu(&ITEMS, 3)),
+        ("menu_scrolled_down", menu(&LONG_ITEMS, 8)),
+        ("menu_scrolled_back_up", {
+            let mut frame = FrameBuffer::new();
+            let mut menu = Menu::with_style(&LONG_ITEMS, 64, MenuStyle::default());
+            menu.select_item(9);
+            menu.select_item(2);
+            menu.draw(&mut frame).unwrap();
+            frame
+        }),
+        ("menu_icons_and_keys", menu(&DECORATED_ITEMS, 1)),
+        ("main_menu", main_menu()),
+    ]
+}
+
+fn main() -> ExitCode {
+    let regenerate = env::args().any(|arg| arg == "--regenerate");
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let goldens = root.join("goldens");
+    let output = root.join("target").join("goldens");
+    fs::create_dir_all(&goldens).unwrap();
+    fs::create_dir_all(&output).unwrap();
+
+    let mut failures = 0;
+    for (name, actual) in scenes() {
+        let golden_path = goldens.join(format!("{name}.txt"));
+
+        if regenerate {
+            fs::write(&golden_path, to_ascii(&actual)).unwrap();
+            println!("{name}: written");
+            continue;
+        }
+
+        let expected = fs::read_to_string(&golden_path)
+            .ok()
+            .and_then(|ascii| from_ascii(&ascii));
+        let Some(expected) = expected else {
+            println!(
+                "{name}: missing or malformed golden {}",
+                golden_path.display()
+            );
+            fs::write(output.join(format!("{name}.actual.png")), to_png(&actual)).unwrap();
+            failures += 1;
+            continue;
+        };
+
+        if expected == actual {
+            println!("{name}: ok");
+            continue;
+        }
+
+        let diff = expected.diff(&actual);
+        println!("{name}: {} pixels differ", diff.count_lit());
+        fs::write(output.join(format!("{name}.actual.png")), to_png(&actual)).unwrap();
+        fs::write(output.join(format!("{name}.diff.png")), to_png(&diff)).unwrap();
+        failures += 1;
+    }
+
+    if failures > 0 {
+        println!(
+            "{failures} golden(s) failed, see {} or rerun with --regenerate",
+            output.display()
+        );
+        return ExitCode::FAILURE;
+    }
+
+    ExitCode::SUCCESS
+}
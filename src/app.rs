@@ -0,0 +1,109 @@
+use embassy_sync::{
+    blocking_mutex::raw::CriticalSectionRawMutex,
+    pubsub::{PubSubBehavior, PubSubChannel},
+};
+
+use crate::{
+    chip8::Chip8App,
+    clock::ClockApp,
+    diagnostics::DiagnosticsApp,
+    hal::{Leds, Platform},
+    input_handler::NUM_KEYS,
+    menu::{Icon, MenuItem},
+    status_bar,
+};
+
+#[derive(Clone, Copy)]
+pub enum AppEvent {
+    Entered(&'static str),
+    Suspended(&'static str),
+    Resumed(&'static str),
+    Exited(&'static str),
+}
+
+/// Lifecycle notifications for background tasks that need to react to the
+/// foreground app, e.g. `color_fader_task` stops the key press animation while
+/// an app is suspended.
+pub static APP_EVENTS: PubSubChannel<CriticalSectionRawMutex, AppEvent, 4, 4, 1> =
+    PubSubChannel::new();
+
+pub struct AppContext<'a, P: Platform> {
+    pub display: &'a mut P::Display,
+    pub input: &'a mut P::Input,
+    pub leds: &'a mut P::Leds,
+    pub audio: &'a mut P::Audio,
+    pub clock: &'a mut P::Clock,
+}
+
+impl<P: Platform> AppContext<'_, P> {
+    /// Suspends `app`, e.g. while a pause overlay is shown.
+    pub fn suspend<A: App>(&mut self, app: &mut A) {
+        app.on_suspend();
+        APP_EVENTS.publish_immediate(AppEvent::Suspended(A::NAME));
+    }
+
+    pub fn resume<A: App>(&mut self, app: &mut A) {
+        APP_EVENTS.publish_immediate(AppEvent::Resumed(A::NAME));
+        app.on_resume();
+    }
+}
+
+pub trait App: Default {
+    const NAME: &'static str;
+    const ICON: Option<&'static Icon> = None;
+
+    async fn run<P: Platform>(&mut self, context: &mut AppContext<'_, P>);
+
+    fn on_enter(&mut self) {}
+    fn on_suspend(&mut self) {}
+    fn on_resume(&mut self) {}
+    fn on_exit(&mut self) {}
+}
+
+async fn launch_app<A: App, P: Platform>(context: &mut AppContext<'_, P>) {
+    let mut app = A::default();
+
+    status_bar::set_mode(A::NAME);
+    app.on_enter();
+    APP_EVENTS.publish_immediate(AppEvent::Entered(A::NAME));
+
+    app.run(context).await;
+
+    APP_EVENTS.publish_immediate(AppEvent::Exited(A::NAME));
+    app.on_exit();
+    context.leds.release_all();
+}
+
+macro_rules! registry {
+    ($($app:ty),* $(,)?) => {
+        pub const NUM_APPS: usize = [$(<$app>::NAME),*].len();
+
+        /// Main menu entries, one per registered app. The first twelve apps can
+        /// be selected with the key of the same index.
+        pub const MENU: [MenuItem<'static>; NUM_APPS] = {
+            let entries: [(&str, Option<&Icon>); NUM_APPS] = [$((<$app>::NAME, <$app>::ICON)),*];
+            let mut items = [MenuItem::new(""); NUM_APPS];
+            let mut i = 0;
+            while i < NUM_APPS {
+                items[i] = MenuItem {
+                    label: entries[i].0,
+                    icon: entries[i].1,
+                    key: if i < NUM_KEYS { Some(i) } else { None },
+                };
+                i += 1;
+            }
+            items
+        };
+
+        pub async fn launch<P: Platform>(index: usize, context: &mut AppContext<'_, P>) {
+            let name = MENU.get(index).map(|item| item.label);
+            $(
+                if name == Some(<$app>::NAME) {
+                    return launch_app::<$app, P>(context).await;
+                }
+            )*
+        }
+    };
+}
+
+registry!(Chip8App, ClockApp, DiagnosticsApp);
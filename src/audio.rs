@@ -0,0 +1,49 @@
+use embassy_rp::{
+    gpio::Output,
+    pwm::{self, Pwm},
+};
+use fixed::FixedU16;
+
+use crate::{hal::Audio, power::clk_sys_freq};
+
+const DIVIDER: u16 = 40;
+
+pub struct Speaker {
+    pwm: Pwm<'static>,
+    enable: Output<'static>,
+    config: pwm::Config,
+}
+
+impl Speaker {
+    pub fn new(pwm: Pwm<'static>, mut enable: Output<'static>) -> Self {
+        enable.set_low();
+
+        let mut config: pwm::Config = Default::default();
+        config.divider = FixedU16::from_num(DIVIDER);
+
+        let mut speaker = Speaker {
+            pwm,
+            enable,
+            config,
+        };
+        speaker.off();
+
+        speaker
+    }
+}
+
+impl Audio for Speaker {
+    fn tone(&mut self, frequency: u32) {
+        self.config.top =
+            (clk_sys_freq() as f64 / f64::from(self.config.divider) / frequency as f64) as u16;
+        self.config.compare_a = self.config.top / 2;
+        self.pwm.set_config(&self.config);
+        self.enable.set_high();
+    }
+
+    fn off(&mut self) {
+        self.enable.set_low();
+        self.config.compare_a = 0;
+        self.pwm.set_config(&self.config);
+    }
+}
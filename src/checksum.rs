@@ -0,0 +1,27 @@
+//! The CRC-32 of zlib and PNG, used for save states and the host's PNG chunks.
+
+pub fn crc32(bytes: impl IntoIterator<Item = u8>) -> u32 {
+    let mut crc = 0xffff_ffff_u32;
+    for byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_value() {
+        assert_eq!(crc32(*b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32([]), 0);
+    }
+}
@@ -1,71 +1,703 @@
-use chip8::{Chip8, Error};
-use embassy_rp::clocks::RoscRng;
-use embassy_sync::pubsub::{DynSubscriber, WaitResult};
-use embassy_time::{Duration, Ticker};
+use core::{
+    fmt::Write,
+    mem,
+    sync::atomic::{AtomicBool, AtomicU8, Ordering},
+};
+
+pub use chip8::{Quirks, Variant};
+
+use chip8::{Chip8, Error, MEMORY_SIZE};
+use embassy_time::{Duration, Instant, Ticker};
 use embedded_graphics::{
-    image::ImageRaw,
+    draw_target::DrawTargetExt,
+    image::{Image, ImageRaw},
+    mono_font::{
+        ascii::{FONT_4X6, FONT_6X10},
+        MonoTextStyle,
+    },
     pixelcolor::BinaryColor,
-    prelude::{ImageDrawable, Point, Size},
-    primitives::{Primitive, PrimitiveStyleBuilder, Rectangle},
+    prelude::{OriginDimensions, Point, PointsIter, Size},
+    primitives::{Primitive, PrimitiveStyle, Rectangle},
+    text::{Baseline, Text},
     Drawable,
 };
+use heapless::{String, Vec};
 use rand::rngs::SmallRng;
-use rand::Rng;
-use sh1106::{interface::DisplayInterface, prelude::GraphicsMode};
+use smart_leds::RGB8;
 
 use crate::{
-    input_handler::{InputEvent, InputSource},
-    INPUT_CHANNEL,
+    app::{App, AppContext},
+    dialog,
+    disassembler::disassemble,
+    error,
+    hal::{Audio, Display, Input, Leds, Platform},
+    icons, info,
+    input_handler::{InputEvent, InputSource, NUM_KEYS},
+    menu::{Icon, MenuItem, MenuManager},
+    oled, preferences,
+    rom_store::{self, StoredRom},
+    save_state::{self, STATES_PER_ROM},
+    status_bar,
+    text_entry::TextEntry,
 };
 
-const VRAM_WIDTH: usize = 64;
-const VRAM_HEIGHT: usize = 32;
+/// The CHIP-8 key each macropad key presses, `None` for unmapped keys. The
+/// `shifted` layer applies while the encoder button is held.
+#[derive(Clone, Copy)]
+pub struct Keymap {
+    pub keys: [Option<u8>; NUM_KEYS],
+    pub shifted: [Option<u8>; NUM_KEYS],
+}
+
+impl Keymap {
+    /// The CHIP-8 keypad laid out on the macropad keys, with its last column,
+    /// C to F, on the right column of the shift layer.
+    #[rustfmt::skip]
+    pub const DEFAULT: Keymap = Keymap {
+        keys: [
+            Some(0x1), Some(0x2), Some(0x3),
+            Some(0x4), Some(0x5), Some(0x6),
+            Some(0x7), Some(0x8), Some(0x9),
+            Some(0xa), Some(0x0), Some(0xb),
+        ],
+        shifted: [
+            None, None, Some(0xc),
+            None, None, Some(0xd),
+            None, None, Some(0xe),
+            None, None, Some(0xf),
+        ],
+    };
+
+    pub fn get(&self, key: usize, shifted: bool) -> Option<u8> {
+        let layer = if shifted { &self.shifted } else { &self.keys };
+        layer.get(key).copied().flatten()
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Rom {
+    name: &'static str,
+    data: &'static [u8],
+    keymap: Keymap,
+    /// Instructions per frame.
+    speed: u32,
+    variant: Variant,
+    quirks: Quirks,
+    /// Frequency of the beep in Hz.
+    tone: u32,
+    /// The [`rom_store`] slot of uploaded ROMs, `None` for the built-in games.
+    slot: Option<usize>,
+}
+
+// `ROMS` and `ROM_MENU`, generated by `build.rs` from the games in `roms/`.
+include!(concat!(env!("OUT_DIR"), "/roms.rs"));
+
+const DEFAULT_SPEED: u32 = 10;
+const DEFAULT_TONE: u32 = 440;
+
+/// Range of instructions per frame the encoder adjusts the speed in.
+const MIN_SPEED: u32 = 1;
+const MAX_SPEED: u32 = 100;
+/// How long the speed stays on screen after turning the encoder.
+const SPEED_OVERLAY_DURATION: Duration = Duration::from_secs(1);
+
+/// Rows taken by the debugger's registers, the game goes below them.
+const DEBUGGER_HEIGHT: u32 = 32;
+/// Lights the keys holding a CHIP-8 key down while debugging.
+const DEBUG_KEY_COLOR: RGB8 = RGB8::new(0, 96, 255);
+
+/// The games plus the sound toggle.
+const MAX_ITEMS: usize = ROMS.len() + rom_store::SLOTS + 1;
+
+const SOUND_ON: MenuItem = MenuItem::new("Sound: on");
+const SOUND_OFF: MenuItem = MenuItem::new("Sound: off");
+
+static MUTED: AtomicBool = AtomicBool::new(false);
+/// Index of the [`DisplayMode`] last chosen.
+static DISPLAY_MODE: AtomicU8 = AtomicU8::new(0);
+
+/// How the CHIP-8 screen is shown on the panel. Screens too large for the
+/// content area, like the 128x64 SUPER-CHIP one, are always `Scaled`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DisplayMode {
+    /// Scaled up by the largest whole factor that fits the panel, 2x for a
+    /// 64x32 screen on the 128x64 OLED. This leaves no room for the status bar.
+    Scaled,
+    /// Unscaled in the middle of the content area.
+    Centered,
+    /// Unscaled on the left, with the speed and frame rate on the right.
+    Hud,
+}
+
+impl DisplayMode {
+    const ALL: [DisplayMode; 3] = [DisplayMode::Scaled, DisplayMode::Centered, DisplayMode::Hud];
+
+    fn label(self) -> &'static str {
+        match self {
+            DisplayMode::Scaled => "Display: full",
+            DisplayMode::Centered => "Display: 1x",
+            DisplayMode::Hud => "Display: HUD",
+        }
+    }
+
+    fn next(self) -> Self {
+        Self::ALL[(self as usize + 1) % Self::ALL.len()]
+    }
+
+    fn shows_status_bar(self) -> bool {
+        self != DisplayMode::Scaled
+    }
+}
+
+/// The built-in games followed by the uploaded ones, and the sound toggle.
+struct Catalog {
+    items: Vec<MenuItem<'static>, MAX_ITEMS>,
+    stored: Vec<StoredRom<'static>, { rom_store::SLOTS }>,
+}
+
+impl Catalog {
+    fn load() -> Self {
+        let stored = rom_store::list();
+        let mut items: Vec<MenuItem, MAX_ITEMS> = ROM_MENU.iter().copied().collect();
+        for rom in &stored {
+            let item = MenuItem::new(rom.name);
+            let item = if items.len() < NUM_KEYS {
+                item.with_key(items.len())
+            } else {
+                item
+            };
+            let _ = items.push(item);
+        }
+        let _ = items.push(if MUTED.load(Ordering::Relaxed) {
+            SOUND_OFF
+        } else {
+            SOUND_ON
+        });
+
+        Catalog { items, stored }
+    }
+
+    /// The game for menu item `choice`, `None` for the sound toggle.
+    fn rom(&self, choice: usize) -> Option<Rom> {
+        match choice.checked_sub(ROMS.len()) {
+            None => Some(ROMS[choice]),
+            Some(index) => self.stored.get(index).map(|stored| Rom {
+                name: stored.name,
+                data: stored.data,
+                keymap: Keymap::DEFAULT,
+                speed: DEFAULT_SPEED,
+                variant: variant_for(stored.name),
+                quirks: Quirks::default(),
+                tone: DEFAULT_TONE,
+                slot: Some(stored.slot),
+            }),
+        }
+    }
+}
+
+/// Uploaded ROMs have no `.meta` file, so their variant goes by the usual file
+/// extensions, `.sc8` for SUPER-CHIP and `.xo8` for XO-CHIP.
+fn variant_for(name: &str) -> Variant {
+    if name.ends_with(".sc8") {
+        Variant::SuperChip
+    } else if name.ends_with(".xo8") {
+        Variant::XoChip
+    } else {
+        Variant::Chip8
+    }
+}
 
-pub struct Chip8Harness<'i> {
-    input_subscriber: DynSubscriber<'i, InputEvent>,
+#[derive(Default)]
+pub struct Chip8App;
+
+impl App for Chip8App {
+    const NAME: &'static str = "Chip-8 Emulator";
+    const ICON: Option<&'static Icon> = Some(&icons::GAMEPAD);
+
+    async fn run<P: Platform>(&mut self, context: &mut AppContext<'_, P>) {
+        let content_height = status_bar::content_area(context.display.size()).size.height;
+
+        let rom = loop {
+            let catalog = Catalog::load();
+            let Some(choice) = MenuManager::new(&catalog.items, content_height)
+                .choose(context.display, context.input)
+                .await
+            else {
+                return;
+            };
+
+            match catalog.rom(choice) {
+                Some(rom) => break rom,
+                None => MUTED.store(!MUTED.load(Ordering::Relaxed), Ordering::Relaxed),
+            }
+        };
+
+        self.run_rom(context, rom).await;
+    }
+}
+
+/// Entries of the menu shown while a game is paused.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PauseAction {
+    Resume,
+    Reset,
+    SaveState,
+    LoadState,
+    Keymap,
+    Display,
+    Debugger,
+    Breakpoint,
+    Rename,
+    Quit,
+}
+
+impl PauseAction {
+    const ALL: [PauseAction; 10] = [
+        PauseAction::Resume,
+        PauseAction::Reset,
+        PauseAction::SaveState,
+        PauseAction::LoadState,
+        PauseAction::Keymap,
+        PauseAction::Display,
+        PauseAction::Debugger,
+        PauseAction::Breakpoint,
+        PauseAction::Rename,
+        PauseAction::Quit,
+    ];
+
+    fn label(self, keypad: bool, harness: &Chip8Harness) -> &'static str {
+        match self {
+            PauseAction::Resume => "Resume",
+            PauseAction::Reset => "Reset",
+            PauseAction::SaveState => "Save state",
+            PauseAction::LoadState => "Load state",
+            PauseAction::Keymap if keypad => "Keys: keypad",
+            PauseAction::Keymap => "Keys: game",
+            PauseAction::Display => harness.display_mode().label(),
+            PauseAction::Debugger if harness.debugging() => "Debugger: on",
+            PauseAction::Debugger => "Debugger: off",
+            PauseAction::Breakpoint => "Set breakpoint",
+            PauseAction::Rename => "Rename",
+            PauseAction::Quit => "Quit",
+        }
+    }
+}
+
+impl Chip8App {
+    /// Runs `rom` with its saved speed, and reports the error if it can't be
+    /// loaded or the emulator fails on it.
+    async fn run_rom<P: Platform>(&mut self, context: &mut AppContext<'_, P>, mut rom: Rom) {
+        let speed = preferences::speed(rom.name).unwrap_or(rom.speed);
+        info!(
+            "starting {} as {:?} at {} instructions per frame, {:?}",
+            rom.name, rom.variant, speed, rom.quirks
+        );
+        let tone = (!MUTED.load(Ordering::Relaxed)).then_some(rom.tone);
+        let display_mode = DisplayMode::ALL[DISPLAY_MODE.load(Ordering::Relaxed) as usize];
+        let mut harness = Chip8Harness::new(P::random_seed(), rom.variant, rom.quirks)
+            .with_tone(tone)
+            .with_speed(speed)
+            .with_display_mode(display_mode);
+        let result = self.play(context, &mut rom, &mut harness).await;
+        context.audio.off();
+        status_bar::set_frame_time(None);
+        if harness.speed() != speed {
+            info!("saving speed {} for {}", harness.speed(), rom.name);
+            let _ = preferences::set_speed(rom.name, harness.speed());
+        }
+
+        if let Err(e) = result {
+            let mut message: String<96> = String::new();
+            let _ = write!(message, "{} stopped: {:?}", rom.name, e);
+            dialog::message_box(context.display, context.input, "Error", &message).await;
+        }
+    }
+
+    /// Runs `rom` until it's quit from the pause menu.
+    async fn play<P: Platform>(
+        &mut self,
+        context: &mut AppContext<'_, P>,
+        rom: &mut Rom,
+        harness: &mut Chip8Harness,
+    ) -> Result<(), Error> {
+        let content_height = status_bar::content_area(context.display.size()).size.height;
+        // Whether the plain CHIP-8 keypad replaces the game's keymap.
+        let mut keypad = false;
+        harness.load_rom(rom.data)?;
+
+        loop {
+            context.display.clear_buffer();
+            let keymap = if keypad { Keymap::DEFAULT } else { rom.keymap };
+            harness
+                .run(
+                    keymap,
+                    context.display,
+                    context.input,
+                    context.audio,
+                    context.leds,
+                )
+                .await?;
+
+            context.suspend(self);
+            let mut selected = 0;
+            let action = loop {
+                // Only uploaded ROMs can be renamed.
+                let actions: Vec<PauseAction, { PauseAction::ALL.len() }> = PauseAction::ALL
+                    .into_iter()
+                    .filter(|&action| action != PauseAction::Rename || rom.slot.is_some())
+                    .collect();
+                let items: Vec<MenuItem, { PauseAction::ALL.len() }> = actions
+                    .iter()
+                    .map(|&action| {
+                        MenuItem::new(action.label(keypad, harness)).with_key(action as usize)
+                    })
+                    .collect();
+                let mut menu = MenuManager::new(&items, content_height);
+                menu.select_item(selected);
+                let Some(choice) = menu.choose(context.display, context.input).await else {
+                    break PauseAction::Quit;
+                };
+
+                match actions[choice] {
+                    PauseAction::Keymap => keypad = !keypad,
+                    PauseAction::Display => {
+                        let display_mode = harness.display_mode().next();
+                        DISPLAY_MODE.store(display_mode as u8, Ordering::Relaxed);
+                        harness.set_display_mode(display_mode);
+                    }
+                    PauseAction::Debugger => harness.set_debugging(!harness.debugging()),
+                    PauseAction::Breakpoint => {
+                        let mut address: String<3> = String::new();
+                        if let Some(breakpoint) = harness.breakpoint() {
+                            let _ = write!(address, "{:03X}", breakpoint);
+                        }
+                        let mut entry = TextEntry::<3>::new("Break at (hex)").with_text(&address);
+                        if let Some(text) = entry.run(context.display, context.input).await {
+                            match u16::from_str_radix(&text, 16) {
+                                Ok(address) => harness.set_breakpoint(Some(address)),
+                                Err(_) if text.is_empty() => harness.set_breakpoint(None),
+                                Err(_) => {
+                                    dialog::toast(
+                                        context.display,
+                                        "Not an address",
+                                        dialog::TOAST_DURATION,
+                                    )
+                                    .await;
+                                }
+                            }
+                        }
+                    }
+                    PauseAction::Rename => {
+                        let mut entry = TextEntry::<{ rom_store::NAME_LEN }>::new("New name")
+                            .with_text(rom.name);
+                        if let Some(name) = entry.run(context.display, context.input).await {
+                            let renamed = rom.slot.and_then(|slot| {
+                                rom_store::rename(slot, &name).ok()?;
+                                rom_store::list()
+                                    .into_iter()
+                                    .find(|stored| stored.slot == slot)
+                            });
+                            let message = match renamed {
+                                Some(stored) => {
+                                    rom.name = stored.name;
+                                    "Renamed"
+                                }
+                                None => "Renaming failed",
+                            };
+                            dialog::toast(context.display, message, dialog::TOAST_DURATION).await;
+                        }
+                    }
+                    PauseAction::SaveState => {
+                        let Some(number) =
+                            choose_state(context.display, context.input, rom.name).await
+                        else {
+                            selected = choice;
+                            continue;
+                        };
+                        let message = match harness.save_state(rom.name, number) {
+                            Ok(()) => "State saved",
+                            Err(()) => "Saving failed",
+                        };
+                        dialog::toast(context.display, message, dialog::TOAST_DURATION).await;
+                        break PauseAction::Resume;
+                    }
+                    PauseAction::LoadState => {
+                        let Some(number) =
+                            choose_state(context.display, context.input, rom.name).await
+                        else {
+                            selected = choice;
+                            continue;
+                        };
+                        if harness.load_state(rom.name, number) {
+                            break PauseAction::Resume;
+                        }
+                        dialog::toast(context.display, "No saved state", dialog::TOAST_DURATION)
+                            .await;
+                    }
+                    action => break action,
+                }
+                selected = choice;
+            };
+            context.resume(self);
+
+            match action {
+                PauseAction::Reset => {
+                    harness.reset(P::random_seed());
+                    harness.load_rom(rom.data)?;
+                }
+                PauseAction::Quit => return Ok(()),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Lets the user pick one of the save states of the ROM `name`, `None` to go
+/// back.
+async fn choose_state<D, I>(display: &mut D, input: &mut I, name: &str) -> Option<usize>
+where
+    D: Display,
+    I: Input,
+{
+    let content_height = status_bar::content_area(display.size()).size.height;
+    let saved = save_state::saved(name);
+    let labels: [String<16>; STATES_PER_ROM] = core::array::from_fn(|number| {
+        let mut label = String::new();
+        let state = if saved[number] { "saved" } else { "empty" };
+        let _ = write!(label, "Slot {}: {}", number + 1, state);
+        label
+    });
+
+    let mut items: Vec<MenuItem, { STATES_PER_ROM + 1 }> = labels
+        .iter()
+        .enumerate()
+        .map(|(number, label)| MenuItem::new(label).with_key(number))
+        .collect();
+    let _ = items.push(MenuItem::new("Back").with_key(STATES_PER_ROM));
+
+    let choice = MenuManager::new(&items, content_height)
+        .choose(display, input)
+        .await?;
+    (choice < STATES_PER_ROM).then_some(choice)
+}
+
+pub struct Chip8Harness {
     emulator: Chip8<SmallRng>,
+    variant: Variant,
+    quirks: Quirks,
     active_keys: [bool; 16],
+    /// The CHIP-8 key each held macropad key pressed, so that releasing it
+    /// releases the same key even if the shift layer changed in between.
+    pressed: [Option<u8>; NUM_KEYS],
+    shift_held: bool,
+    /// Whether a key was pressed on the shift layer since the button went down.
+    shift_used: bool,
+    /// Frequency to beep at while the sound timer runs, `None` if muted.
+    tone: Option<u32>,
+    beeping: bool,
+    /// Instructions per frame.
+    speed: u32,
+    /// Until when the speed overlay is shown.
+    speed_shown_until: Option<Instant>,
+    display_mode: DisplayMode,
+    /// Size of the CHIP-8 screen last drawn, SUPER-CHIP games switch between
+    /// 64x32 and 128x64.
+    screen_size: Size,
+    /// Frames run since `fps_counted_since`, and how many the last second had.
+    frames: u32,
+    fps_counted_since: Instant,
+    fps: u32,
+    /// Whether the debugger replaces the game screen.
+    debugging: bool,
+    /// Whether the debugger stopped the emulator, which then only runs the
+    /// `steps` asked for with the encoder.
+    halted: bool,
+    steps: u32,
+    breakpoint: Option<u16>,
 }
 
-impl<'i> Chip8Harness<'i> {
-    pub fn new() -> Self {
-        let input_subscriber = INPUT_CHANNEL.dyn_subscriber().unwrap();
-        let emulator = Chip8::new(RoscRng.gen::<u64>());
+impl Chip8Harness {
+    pub fn new(seed: u64, variant: Variant, quirks: Quirks) -> Self {
+        let emulator = Chip8::new(seed, variant, quirks);
         let active_keys = [false; 16];
 
         Chip8Harness {
-            input_subscriber,
             emulator,
+            variant,
+            quirks,
             active_keys,
+            pressed: [None; NUM_KEYS],
+            shift_held: false,
+            shift_used: false,
+            tone: Some(DEFAULT_TONE),
+            beeping: false,
+            speed: DEFAULT_SPEED,
+            speed_shown_until: None,
+            display_mode: DisplayMode::Scaled,
+            screen_size: Size::zero(),
+            frames: 0,
+            fps_counted_since: Instant::now(),
+            fps: 0,
+            debugging: false,
+            halted: false,
+            steps: 0,
+            breakpoint: None,
         }
     }
 
-    pub async fn run<DI>(
+    pub fn speed(&self) -> u32 {
+        self.speed
+    }
+
+    /// Lets go of everything held, as the releases go to the pause menu.
+    fn pause<A: Audio, L: Leds>(&mut self, audio: &mut A, leds: &mut L) {
+        self.active_keys = [false; 16];
+        self.pressed = [None; NUM_KEYS];
+        self.beeping = false;
+        self.speed_shown_until = None;
+        audio.off();
+        leds.release_all();
+    }
+
+    fn adjust_speed(&mut self, delta: i32) {
+        self.speed = self
+            .speed
+            .saturating_add_signed(delta)
+            .clamp(MIN_SPEED, MAX_SPEED);
+        self.speed_shown_until = Some(Instant::now() + SPEED_OVERLAY_DURATION);
+    }
+
+    pub fn with_tone(mut self, tone: Option<u32>) -> Self {
+        self.tone = tone;
+        self
+    }
+
+    pub fn with_speed(mut self, speed: u32) -> Self {
+        self.speed = speed.clamp(MIN_SPEED, MAX_SPEED);
+        self
+    }
+
+    pub fn with_display_mode(mut self, display_mode: DisplayMode) -> Self {
+        self.display_mode = display_mode;
+        self
+    }
+
+    pub fn display_mode(&self) -> DisplayMode {
+        self.display_mode
+    }
+
+    pub fn set_display_mode(&mut self, display_mode: DisplayMode) {
+        self.display_mode = display_mode;
+    }
+
+    pub fn debugging(&self) -> bool {
+        self.debugging
+    }
+
+    /// Shows or hides the debugger, which starts out halted.
+    pub fn set_debugging(&mut self, debugging: bool) {
+        self.debugging = debugging;
+        self.halted = debugging;
+        self.steps = 0;
+    }
+
+    pub fn breakpoint(&self) -> Option<u16> {
+        self.breakpoint
+    }
+
+    /// Halts in the debugger once the PC reaches `breakpoint`.
+    pub fn set_breakpoint(&mut self, breakpoint: Option<u16>) {
+        self.breakpoint = breakpoint;
+    }
+
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), Error> {
+        self.emulator.load_rom(rom).inspect_err(|e| {
+            error!("loading the ROM failed: {:?}", e);
+        })?;
+        info!("loaded {} byte ROM", rom.len());
+
+        Ok(())
+    }
+
+    /// Starts over with a fresh emulator, the ROM has to be loaded again.
+    pub fn reset(&mut self, seed: u64) {
+        self.emulator = Chip8::new(seed, self.variant, self.quirks);
+    }
+
+    /// Saves the emulator as state `number` of the ROM `name`.
+    pub fn save_state(&mut self, name: &str, number: usize) -> Result<(), ()> {
+        save_state::save(name, number, &self.emulator.state())
+    }
+
+    /// Returns to state `number` of the ROM `name`, `false` if there is none.
+    pub fn load_state(&mut self, name: &str, number: usize) -> bool {
+        let Some(state) = save_state::load(name, number) else {
+            return false;
+        };
+        self.emulator.restore(&state);
+
+        true
+    }
+
+    /// Runs the loaded ROM until the encoder button is tapped to pause it.
+    /// In the debugger the encoder halts and single-steps the emulator
+    /// clockwise and lets it run again counterclockwise.
+    pub async fn run<D, I, A, L>(
         &mut self,
-        rom: &[u8],
-        keymap: [usize; 12],
-        display: &mut GraphicsMode<DI>,
+        keymap: Keymap,
+        display: &mut D,
+        input: &mut I,
+        audio: &mut A,
+        leds: &mut L,
     ) -> Result<(), Error>
     where
-        DI: DisplayInterface,
-        <DI as DisplayInterface>::Error: core::fmt::Debug,
+        D: Display,
+        I: Input,
+        A: Audio,
+        L: Leds,
     {
-        self.emulator.load_rom(rom)?;
         let mut ticker = Ticker::every(Duration::from_micros(16_667));
 
+        let content_area = status_bar::content_area(display.size());
+        let mut status_bar_refreshed: Option<Instant> = None;
+
         loop {
-            if let Some(wait_result) = self.input_subscriber.try_next_message() {
-                match wait_result {
-                    WaitResult::Message(InputEvent::Pressed(InputSource::Key(key))) => {
-                        self.active_keys[keymap[key]] = true;
+            if let Some(event) = input.try_next_event() {
+                match event {
+                    InputEvent::Pressed(InputSource::Key(key)) => {
+                        self.shift_used |= self.shift_held;
+                        if let Some(chip8_key) = keymap.get(key, self.shift_held) {
+                            self.active_keys[chip8_key as usize] = true;
+                            self.pressed[key] = Some(chip8_key);
+                        }
+                    }
+                    InputEvent::Released(InputSource::Key(key)) => {
+                        if let Some(chip8_key) = self.pressed.get_mut(key).and_then(Option::take) {
+                            self.active_keys[chip8_key as usize] = false;
+                        }
+                    }
+                    InputEvent::Pressed(InputSource::Button) => {
+                        self.shift_held = true;
+                        self.shift_used = false;
                     }
-                    WaitResult::Message(InputEvent::Released(InputSource::Key(key))) => {
-                        self.active_keys[keymap[key]] = false;
+                    // A tap on the button without using the shift layer
+                    // pauses. The release of the press that resumed the game
+                    // from the pause menu is ignored.
+                    InputEvent::Released(InputSource::Button) => {
+                        let tapped = self.shift_held && !self.shift_used;
+                        self.shift_held = false;
+                        if tapped {
+                            self.pause(audio, leds);
+                            return Ok(());
+                        }
                     }
-                    WaitResult::Message(InputEvent::Pressed(InputSource::Button)) => {
-                        return Ok(());
+                    InputEvent::TurnedCW(_) if self.debugging => {
+                        self.steps += self.halted as u32;
+                        self.halted = true;
                     }
-                    _ => {}
+                    InputEvent::TurnedCCW(_) if self.debugging => self.halted = false,
+                    InputEvent::TurnedCW(_) => self.adjust_speed(1),
+                    InputEvent::TurnedCCW(_) => self.adjust_speed(-1),
                 }
                 continue;
             }
@@ -76,15 +708,466 @@ impl<'i> Chip8Harness<'i> {
                 .enumerate()
                 .map(|(i, key)| if *key { 1 << i } else { 0 })
                 .sum();
-            self.emulator.frame(keypad)?;
+            let steps = if self.halted {
+                mem::take(&mut self.steps)
+            } else {
+                self.speed
+            };
+            for _ in 0..steps {
+                self.emulator.step(keypad).inspect_err(|e| {
+                    error!("emulation failed: {:?}", e);
+                })?;
+                if self.breakpoint == Some(self.emulator.pc()) && !self.halted {
+                    info!("breakpoint at {:03X}", self.emulator.pc());
+                    self.set_debugging(true);
+                    break;
+                }
+            }
+            if !self.halted {
+                self.emulator.tick_timers();
+            }
 
-            let framebuffer = self.emulator.fb();
-            ImageRaw::<BinaryColor>::new(&framebuffer, chip8::SCREEN_WIDTH as u32 * 2)
-                .draw(display)
-                .unwrap();
+            // Only touch the PWM when the timer starts or stops.
+            let beeping = self.emulator.sound_timer() > 0;
+            if beeping != self.beeping {
+                self.beeping = beeping;
+                match self.tone {
+                    Some(frequency) if beeping => audio.tone(frequency),
+                    _ => audio.off(),
+                }
+            }
+
+            self.frames += 1;
+            if self.fps_counted_since.elapsed() >= Duration::from_secs(1) {
+                self.fps = self.frames;
+                self.frames = 0;
+                self.fps_counted_since = Instant::now();
+            }
+
+            // The game may not cover the whole panel, so wipe the speed
+            // overlay once it's gone, and what's left of the old screen when
+            // the resolution changes.
+            let (width, height) = self.emulator.screen_size();
+            let screen_size = Size::new(width as u32, height as u32);
+            let overlay_gone = self
+                .speed_shown_until
+                .is_some_and(|until| Instant::now() >= until);
+            if overlay_gone {
+                self.speed_shown_until = None;
+            }
+            if overlay_gone || screen_size != self.screen_size {
+                self.screen_size = screen_size;
+                display.clear_buffer();
+                status_bar_refreshed = None;
+            }
+
+            let drawn = if self.debugging {
+                let drawn = self.draw_debugger(display);
+                self.light_keys(keymap, leds);
+                drawn
+            } else {
+                let display_mode = self.fitting_display_mode(&content_area);
+                let drawn = self.draw_screen(display_mode, display, &content_area);
+
+                if self.speed_shown_until.is_some() {
+                    let mut text: String<16> = String::new();
+                    let _ = write!(text, "Speed {}", self.speed);
+                    dialog::draw_toast(display, &text);
+                }
+
+                let status_bar_due = status_bar_refreshed
+                    .is_none_or(|refreshed| refreshed.elapsed() >= status_bar::REFRESH_INTERVAL);
+                if display_mode.shows_status_bar() && status_bar_due {
+                    status_bar::set_frame_time(Some(oled::frame_stats().frame_time));
+                    status_bar_refreshed = Some(Instant::now());
+                    drawn.and(status_bar::draw(display))
+                } else {
+                    drawn
+                }
+            };
 
-            display.flush().unwrap();
+            // The game goes on, the next frame may well make it to the panel.
+            if let Err(e) = drawn.and_then(|()| display.flush()) {
+                error!("updating the display failed: {:?}", e);
+            }
             ticker.next().await;
         }
     }
+
+    /// The registers and the next instruction over the top rows, with the game
+    /// below at 1x unless it's in hi-res.
+    fn draw_debugger<D: Display>(&mut self, display: &mut D) -> Result<(), D::Error> {
+        let state = self.emulator.state();
+        let pc = state.pc as usize;
+        let opcode = u16::from_be_bytes([
+            state.memory[pc % MEMORY_SIZE],
+            state.memory[(pc + 1) % MEMORY_SIZE],
+        ]);
+
+        let mut text: String<160> = String::new();
+        let _ = writeln!(
+            text,
+            "PC {:03X} I {:03X} SP {:X} DT {:02X} ST {:02X}",
+            state.pc, state.i, state.sp, state.delay_timer, state.sound_timer
+        );
+        let _ = writeln!(text, "{:04X} {}", opcode, disassemble(opcode));
+        for (row, registers) in state.v.chunks(8).enumerate() {
+            let _ = write!(text, "V{:X}", row * 8);
+            for value in registers {
+                let _ = write!(text, " {:02X}", value);
+            }
+            let _ = writeln!(text);
+        }
+        let _ = match self.breakpoint {
+            Some(breakpoint) => write!(text, "BP {:03X}", breakpoint),
+            None => write!(text, "BP -"),
+        };
+        let _ = write!(text, " {}", if self.halted { "halted" } else { "running" });
+
+        display.clear_buffer();
+        Text::with_baseline(
+            &text,
+            Point::zero(),
+            MonoTextStyle::new(&FONT_4X6, BinaryColor::On),
+            Baseline::Top,
+        )
+        .draw(display)?;
+
+        let size = display.size();
+        if self.screen_size.height <= size.height.saturating_sub(DEBUGGER_HEIGHT) {
+            let framebuffer = self.emulator.fb();
+            let screen_len = (self.screen_size.width * self.screen_size.height / 8) as usize;
+            let screen =
+                ImageRaw::<BinaryColor>::new(&framebuffer[..screen_len], self.screen_size.width);
+            let x = (size.width.saturating_sub(self.screen_size.width) / 2) as i32;
+            Image::new(&screen, Point::new(x, DEBUGGER_HEIGHT as i32)).draw(display)?;
+        }
+
+        Ok(())
+    }
+
+    /// Lights every key that presses a CHIP-8 key that's currently down.
+    fn light_keys<L: Leds>(&self, keymap: Keymap, leds: &mut L) {
+        for key in 0..NUM_KEYS {
+            match keymap.get(key, self.shift_held) {
+                Some(chip8_key) if self.active_keys[chip8_key as usize] => {
+                    leds.set(key, DEBUG_KEY_COLOR)
+                }
+                _ => leds.release(key),
+            }
+        }
+    }
+
+    /// The chosen display mode, unless the screen only fits the whole panel.
+    fn fitting_display_mode(&self, content_area: &Rectangle) -> DisplayMode {
+        let fits = self.screen_size.width <= content_area.size.width
+            && self.screen_size.height <= content_area.size.height;
+        if fits {
+            self.display_mode
+        } else {
+            DisplayMode::Scaled
+        }
+    }
+
+    fn draw_screen<D: Display>(
+        &self,
+        display_mode: DisplayMode,
+        display: &mut D,
+        content_area: &Rectangle,
+    ) -> Result<(), D::Error> {
+        let framebuffer = self.emulator.fb();
+        let screen_size = self.screen_size;
+        let screen_len = (screen_size.width * screen_size.height / 8) as usize;
+        let screen = ImageRaw::<BinaryColor>::new(&framebuffer[..screen_len], screen_size.width);
+
+        match display_mode {
+            // One pass over the panel, rather than a square per CHIP-8 pixel.
+            DisplayMode::Scaled => {
+                let size = display.size();
+                let scale = (size.width / screen_size.width)
+                    .min(size.height / screen_size.height)
+                    .max(1);
+                let area = Rectangle::new(Point::zero(), screen_size * scale);
+                let colors = area.points().map(|point| {
+                    let x = point.x as u32 / scale;
+                    let y = point.y as u32 / scale;
+                    screen_pixel(&framebuffer, screen_size.width, x, y)
+                });
+                display.fill_contiguous(&area, colors)
+            }
+            DisplayMode::Centered => {
+                let top_left = Rectangle::with_center(content_area.center(), screen_size).top_left;
+                Image::new(&screen, top_left).draw(display)
+            }
+            DisplayMode::Hud => {
+                let top_left = Point::new(
+                    content_area.top_left.x,
+                    content_area.center().y - screen_size.height as i32 / 2,
+                );
+                Image::new(&screen, top_left).draw(display)?;
+
+                let hud = Rectangle::new(
+                    content_area.top_left + Point::new(screen_size.width as i32, 0),
+                    Size::new(
+                        content_area.size.width.saturating_sub(screen_size.width),
+                        content_area.size.height,
+                    ),
+                );
+                hud.into_styled(PrimitiveStyle::with_fill(BinaryColor::Off))
+                    .draw(display)?;
+                let mut text: String<32> = String::new();
+                let _ = write!(text, "Speed {}\n{} fps", self.speed, self.fps);
+                Text::with_baseline(
+                    &text,
+                    Point::new(hud.top_left.x + 4, top_left.y),
+                    MonoTextStyle::new(&FONT_6X10, BinaryColor::On),
+                    Baseline::Top,
+                )
+                .draw(&mut display.clipped(&hud))?;
+
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A pixel of `framebuffer`, a `width` pixels wide CHIP-8 screen with one bit
+/// per pixel, rows top to bottom and the leftmost pixel in the high bit.
+fn screen_pixel(framebuffer: &[u8], width: u32, x: u32, y: u32) -> BinaryColor {
+    let index = (y * width + x) as usize;
+    let lit = framebuffer
+        .get(index / 8)
+        .is_some_and(|byte| byte & (0x80 >> (index % 8)) != 0);
+
+    BinaryColor::from(lit)
+}
+
+#[cfg(all(test, feature = "host"))]
+mod tests {
+    extern crate std;
+
+    use embassy_futures::block_on;
+
+    use super::*;
+    use crate::{
+        datetime::DateTime,
+        framebuffer::FrameBuffer,
+        hal::host::{to_ascii, Host, HostAudio, HostClock, HostDisplay, HostLeds, ScriptedInput},
+    };
+
+    /// Draws the font's `0` in the top left corner, then loops.
+    const DRAW_ZERO: [u8; 13] = [
+        0xa2, 0x08, // LD I, 208
+        0xd0, 0x15, // DRW V0, V1, 5
+        0x12, 0x04, // JP 204
+        0x00, 0x00, // padding
+        0xf0, 0x90, 0x90, 0x90, 0xf0,
+    ];
+    /// Starts the sound timer, then loops.
+    const BEEP: [u8; 6] = [
+        0x63, 0x08, // LD V3, 08
+        0xf3, 0x18, // LD ST, V3
+        0x12, 0x04, // JP 204
+    ];
+
+    /// Not an instruction on any variant.
+    const INVALID: [u8; 2] = [0xff, 0xff];
+    /// Doesn't fit behind the interpreter area.
+    static TOO_LARGE: [u8; MEMORY_SIZE] = [0; MEMORY_SIZE];
+
+    /// Draws a `0` like `DRAW_ZERO` once CHIP-8 key `key` is held.
+    fn wait_for_key(key: u8) -> [u8; 19] {
+        [
+            0x61, key, // LD V1, key
+            0xe1, 0xa1, // SKNP V1
+            0x12, 0x08, // JP 208
+            0x12, 0x02, // JP 202
+            0xa2, 0x0e, // LD I, 20E
+            0xd0, 0x05, // DRW V0, V0, 5
+            0x12, 0x0c, // JP 20C
+            0xf0, 0x90, 0x90, 0x90, 0xf0,
+        ]
+    }
+
+    fn harness(rom: &[u8]) -> Chip8Harness {
+        let mut harness = Chip8Harness::new(1, Variant::Chip8, Quirks::default());
+        harness.load_rom(rom).unwrap();
+
+        harness
+    }
+
+    /// Idles for `frames` frames, then taps the encoder button to pause.
+    fn paused_after(frames: usize) -> ScriptedInput {
+        let mut input = ScriptedInput::default();
+        input.idle(frames);
+        tap(&mut input);
+
+        input
+    }
+
+    fn tap(input: &mut ScriptedInput) {
+        input
+            .push(InputEvent::Pressed(InputSource::Button))
+            .push(InputEvent::Released(InputSource::Button));
+    }
+
+    /// Runs until `input` pauses, which has to be at its very end.
+    fn run(
+        harness: &mut Chip8Harness,
+        mut input: ScriptedInput,
+    ) -> (HostDisplay, HostAudio, HostLeds) {
+        let mut display = HostDisplay::default();
+        let mut audio = HostAudio::default();
+        let mut leds = HostLeds::default();
+        block_on(harness.run(
+            Keymap::DEFAULT,
+            &mut display,
+            &mut input,
+            &mut audio,
+            &mut leds,
+        ))
+        .unwrap();
+        assert!(input.is_empty());
+
+        (display, audio, leds)
+    }
+
+    #[test]
+    fn screen_is_scaled_to_the_panel() {
+        let (display, _, _) = run(&mut harness(&DRAW_ZERO), paused_after(2));
+
+        let zero = ["####", "#..#", "#..#", "#..#", "####"];
+        let mut expected = std::string::String::new();
+        for y in 0..64 {
+            for x in 0..128 {
+                let lit = y < 10 && x < 8 && zero[y / 2].as_bytes()[x / 2] == b'#';
+                expected.push(if lit { '#' } else { '.' });
+            }
+            expected.push('\n');
+        }
+        assert_eq!(to_ascii(&display.flushed), expected);
+    }
+
+    #[test]
+    fn sound_timer_beeps_until_paused() {
+        let mut harness = harness(&BEEP).with_tone(Some(DEFAULT_TONE));
+        let (_, audio, _) = run(&mut harness, paused_after(2));
+
+        assert_eq!(audio.tones, [DEFAULT_TONE]);
+        assert_eq!(audio.frequency, None);
+    }
+
+    #[test]
+    fn held_key_reaches_the_emulator() {
+        let (display, _, _) = run(&mut harness(&wait_for_key(1)), paused_after(2));
+        assert!(!to_ascii(&display.flushed).contains('#'));
+
+        // Key 0 is CHIP-8 key 1.
+        let mut harness = harness(&wait_for_key(1));
+        let mut input = ScriptedInput::new([InputEvent::Pressed(InputSource::Key(0))]);
+        input.idle(2);
+        tap(&mut input);
+        let (display, _, _) = run(&mut harness, input);
+        assert!(to_ascii(&display.flushed).contains('#'));
+    }
+
+    #[test]
+    fn shift_layer_does_not_pause() {
+        // Key 2 is CHIP-8 key C while the button is held.
+        let mut harness = harness(&wait_for_key(0xc));
+        let mut input = ScriptedInput::new([
+            InputEvent::Pressed(InputSource::Button),
+            InputEvent::Pressed(InputSource::Key(2)),
+        ]);
+        input
+            .idle(2)
+            .push(InputEvent::Released(InputSource::Key(2)))
+            .push(InputEvent::Released(InputSource::Button))
+            .idle(1);
+        tap(&mut input);
+        let (display, _, _) = run(&mut harness, input);
+        assert!(to_ascii(&display.flushed).contains('#'));
+    }
+
+    /// Runs `data` as an uploaded ROM, then presses a key to dismiss whatever
+    /// comes up.
+    fn run_uploaded(data: &'static [u8]) -> HostDisplay {
+        let rom = Rom {
+            name: "Broken",
+            data,
+            keymap: Keymap::DEFAULT,
+            speed: DEFAULT_SPEED,
+            variant: Variant::Chip8,
+            quirks: Quirks::default(),
+            tone: DEFAULT_TONE,
+            slot: Some(0),
+        };
+        let mut display = HostDisplay::default();
+        let mut input = ScriptedInput::default();
+        input.idle(1).push(InputEvent::Pressed(InputSource::Key(0)));
+        let mut context: AppContext<Host> = AppContext {
+            display: &mut display,
+            input: &mut input,
+            leds: &mut HostLeds::default(),
+            audio: &mut HostAudio::default(),
+            clock: &mut HostClock {
+                datetime: DateTime {
+                    year: 2024,
+                    month: 1,
+                    day: 1,
+                    hours: 0,
+                    minutes: 0,
+                    seconds: 0,
+                },
+            },
+        };
+        block_on(Chip8App.run_rom(&mut context, rom));
+        assert!(input.is_empty());
+
+        display
+    }
+
+    /// The rows of `frame` below the status bar, which other tests change.
+    fn content(frame: &FrameBuffer) -> std::string::String {
+        let top = status_bar::content_area(frame.size()).top_left.y as usize;
+        to_ascii(frame)
+            .lines()
+            .skip(top)
+            .collect::<std::vec::Vec<_>>()
+            .join("\n")
+    }
+
+    /// What the message box for `error` looks like.
+    fn error_box(error: Error) -> std::string::String {
+        let mut display = HostDisplay::default();
+        let mut input = ScriptedInput::new([InputEvent::Pressed(InputSource::Key(0))]);
+        let message = std::format!("Broken stopped: {:?}", error);
+        block_on(dialog::message_box(
+            &mut display,
+            &mut input,
+            "Error",
+            &message,
+        ));
+
+        content(&display.flushed)
+    }
+
+    #[test]
+    fn invalid_opcode_is_reported() {
+        let mut emulator = Chip8::<SmallRng>::new(1, Variant::Chip8, Quirks::default());
+        emulator.load_rom(&INVALID).unwrap();
+        let error = emulator.step(0).unwrap_err();
+
+        let display = run_uploaded(&INVALID);
+        assert_eq!(content(&display.flushed), error_box(error));
+    }
+
+    #[test]
+    fn rom_too_large_is_reported() {
+        let mut emulator = Chip8::<SmallRng>::new(1, Variant::Chip8, Quirks::default());
+        let error = emulator.load_rom(&TOO_LARGE).unwrap_err();
+
+        let display = run_uploaded(&TOO_LARGE);
+        assert_eq!(content(&display.flushed), error_box(error));
+    }
 }
@@ -0,0 +1,316 @@
+use core::fmt::Write;
+
+use embedded_graphics::{
+    draw_target::{DrawTarget, DrawTargetExt},
+    mono_font::{ascii::FONT_6X10, MonoTextStyle, MonoTextStyleBuilder},
+    pixelcolor::BinaryColor,
+    prelude::Point,
+    text::{Alignment, Baseline, Text, TextStyleBuilder},
+    Drawable,
+};
+use heapless::String;
+
+use crate::{
+    app::{App, AppContext},
+    datetime::{DateTime, Field},
+    dialog,
+    hal::{Clock, Display, Input, Platform},
+    icons,
+    input_handler::{InputEvent, InputSource},
+    menu::Icon,
+    status_bar,
+};
+
+#[derive(Default)]
+pub struct ClockApp;
+
+impl App for ClockApp {
+    const NAME: &'static str = "Set Date & Time";
+    const ICON: Option<&'static Icon> = Some(&icons::CLOCK);
+
+    async fn run<P: Platform>(&mut self, context: &mut AppContext<'_, P>) {
+        set_interactive(context.display, context.input, context.clock).await;
+    }
+}
+
+pub async fn set_interactive<D, I, C>(display: &mut D, input: &mut I, clock: &mut C)
+where
+    D: Display,
+    I: Input,
+    C: Clock,
+{
+    let Ok(mut current_datetime) = clock.datetime() else {
+        dialog::message_box(
+            display,
+            input,
+            "Error",
+            "Could not read the date & time from the RTC.",
+        )
+        .await;
+        return;
+    };
+    current_datetime.sanitize();
+
+    let Some(new_datetime) = DateTimeEditor::new(current_datetime)
+        .edit(display, input)
+        .await
+    else {
+        return;
+    };
+
+    if dialog::confirm(display, input, "Save the new date & time?").await != Some(true) {
+        return;
+    }
+
+    match clock.set_datetime(&new_datetime) {
+        Ok(()) => {
+            dialog::toast(display, "Date & time saved", dialog::TOAST_DURATION).await;
+        }
+        Err(()) => {
+            dialog::message_box(
+                display,
+                input,
+                "Error",
+                "Could not write the date & time to the RTC.",
+            )
+            .await;
+        }
+    }
+}
+
+struct DateTimeEditor {
+    datetime: DateTime,
+    field: Field,
+}
+
+impl DateTimeEditor {
+    fn new(datetime: DateTime) -> Self {
+        DateTimeEditor {
+            datetime,
+            field: Field::Year,
+        }
+    }
+
+    /// Lets the user edit one field after another, the encoder button moving
+    /// on to the next. Returns `None` if a key is pressed to cancel, or the
+    /// display could not be updated.
+    async fn edit<D, I>(&mut self, display: &mut D, input: &mut I) -> Option<DateTime>
+    where
+        D: Display,
+        I: Input,
+    {
+        let content_area = status_bar::content_area(display.size());
+
+        display.clear_buffer();
+        status_bar::draw(display).ok()?;
+        self.draw(&mut display.cropped(&content_area)).ok()?;
+        display.flush().ok()?;
+
+        loop {
+            match input.next_event().await {
+                InputEvent::Pressed(InputSource::Button) => match self.field.next() {
+                    Some(field) => self.field = field,
+                    None => return Some(self.datetime),
+                },
+                InputEvent::Pressed(InputSource::Key(_)) => return None,
+                InputEvent::TurnedCW(_) => {
+                    self.datetime.increment(self.field);
+                }
+                InputEvent::TurnedCCW(_) => {
+                    self.datetime.decrement(self.field);
+                }
+                _ => continue,
+            }
+
+            self.draw(&mut display.cropped(&content_area)).ok()?;
+            display.flush().ok()?;
+        }
+    }
+}
+
+impl Drawable for DateTimeEditor {
+    type Color = BinaryColor;
+    type Output = ();
+
+    fn draw<D>(&self, target: &mut D) -> Result<Self::Output, D::Error>
+    where
+        D: DrawTarget<Color = Self::Color>,
+    {
+        let font_width = FONT_6X10.character_size.width as i32;
+        let font_height = FONT_6X10.character_size.height as i32;
+        let bounding_box = target.bounding_box();
+        let target_width = bounding_box.size.width as i32;
+        let target_height = bounding_box.size.height as i32;
+
+        let normal_text_style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        let inverted_text_style = MonoTextStyleBuilder::new()
+            .font(&FONT_6X10)
+            .text_color(BinaryColor::Off)
+            .background_color(BinaryColor::On)
+            .build();
+
+        target.clear(BinaryColor::Off)?;
+
+        Text::with_text_style(
+            "Set Date & Time",
+            Point::new(target_width / 2, 0),
+            normal_text_style,
+            TextStyleBuilder::new()
+                .alignment(Alignment::Center)
+                .baseline(Baseline::Top)
+                .build(),
+        )
+        .draw(target)?;
+
+        let mut text: String<19> = String::new();
+        let _ = write!(text, "{}", self.datetime);
+
+        let origin = Point::new(
+            (target_width - text.len() as i32 * font_width) / 2,
+            (target_height - font_height) / 2,
+        );
+        Text::with_baseline(&text, origin, normal_text_style, Baseline::Top).draw(target)?;
+
+        let (offset, len) = self.field.span();
+        Text::with_baseline(
+            &text[offset..offset + len],
+            origin + Point::new(offset as i32 * font_width, 0),
+            inverted_text_style,
+            Baseline::Top,
+        )
+        .draw(target)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "host"))]
+mod tests {
+    extern crate std;
+
+    use std::{string::String, vec::Vec};
+
+    use embassy_futures::block_on;
+    use embedded_graphics::prelude::OriginDimensions;
+
+    use super::*;
+    use crate::{
+        framebuffer::FrameBuffer,
+        hal::host::{to_ascii, HostClock, HostDisplay, ScriptedInput},
+    };
+
+    const BUTTON: InputEvent = InputEvent::Pressed(InputSource::Button);
+
+    fn datetime(day: u8, hours: u8) -> DateTime {
+        DateTime {
+            year: 2024,
+            month: 2,
+            day,
+            hours,
+            minutes: 30,
+            seconds: 0,
+        }
+    }
+
+    /// The rows of `frame` below the status bar, where the editor goes.
+    fn content(frame: &FrameBuffer) -> String {
+        let top = status_bar::content_area(frame.size()).top_left.y as usize;
+        to_ascii(frame)
+            .lines()
+            .skip(top)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn editor_frame(datetime: DateTime, field: Field) -> FrameBuffer {
+        let mut frame = FrameBuffer::new();
+        let content_area = status_bar::content_area(frame.size());
+        DateTimeEditor { datetime, field }
+            .draw(&mut frame.cropped(&content_area))
+            .unwrap();
+
+        frame
+    }
+
+    #[test]
+    fn editor_highlights_the_field_being_changed() {
+        let mut display = HostDisplay::default();
+        // Over to the day, up past the end of February, then one field on.
+        let mut input = ScriptedInput::new([
+            BUTTON,
+            BUTTON,
+            InputEvent::TurnedCW(1),
+            InputEvent::TurnedCW(1),
+            BUTTON,
+            InputEvent::TurnedCCW(1),
+            InputEvent::Pressed(InputSource::Key(0)),
+        ]);
+        let mut editor = DateTimeEditor::new(datetime(28, 10));
+        let edited = block_on(editor.edit(&mut display, &mut input));
+
+        assert_eq!(edited, None);
+        assert_eq!(
+            content(&display.flushed),
+            content(&editor_frame(
+                DateTime {
+                    month: 3,
+                    ..datetime(1, 9)
+                },
+                Field::Hours
+            ))
+        );
+        assert_ne!(
+            content(&editor_frame(datetime(1, 9), Field::Hours)),
+            content(&editor_frame(datetime(1, 9), Field::Day))
+        );
+    }
+
+    #[test]
+    fn confirmed_edit_sets_the_clock() {
+        let mut display = HostDisplay::default();
+        let mut clock = HostClock {
+            datetime: datetime(28, 10),
+        };
+        let mut input = ScriptedInput::new([
+            BUTTON,
+            BUTTON,
+            InputEvent::TurnedCW(1),
+            BUTTON,
+            BUTTON,
+            BUTTON,
+            BUTTON,
+            // Yes, save it.
+            BUTTON,
+        ]);
+        block_on(set_interactive(&mut display, &mut input, &mut clock));
+
+        assert!(input.is_empty());
+        assert_eq!(clock.datetime, datetime(29, 10));
+    }
+
+    #[test]
+    fn cancelled_edit_leaves_the_clock() {
+        let mut display = HostDisplay::default();
+        let mut clock = HostClock {
+            datetime: datetime(28, 10),
+        };
+        let mut input = ScriptedInput::new([
+            InputEvent::TurnedCW(1),
+            InputEvent::Pressed(InputSource::Key(9)),
+        ]);
+        block_on(set_interactive(&mut display, &mut input, &mut clock));
+
+        assert!(input.is_empty());
+        assert_eq!(clock.datetime, datetime(28, 10));
+        assert_eq!(
+            content(&display.flushed),
+            content(&editor_frame(
+                DateTime {
+                    year: 2025,
+                    ..datetime(28, 10)
+                },
+                Field::Year
+            ))
+        );
+    }
+}
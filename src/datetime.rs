@@ -0,0 +1,310 @@
+use core::fmt;
+
+pub const MIN_YEAR: u16 = 2000;
+pub const MAX_YEAR: u16 = 2099;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Field {
+    Year,
+    Month,
+    Day,
+    Hours,
+    Minutes,
+    Seconds,
+}
+
+impl Field {
+    pub fn next(self) -> Option<Field> {
+        match self {
+            Field::Year => Some(Field::Month),
+            Field::Month => Some(Field::Day),
+            Field::Day => Some(Field::Hours),
+            Field::Hours => Some(Field::Minutes),
+            Field::Minutes => Some(Field::Seconds),
+            Field::Seconds => None,
+        }
+    }
+
+    /// Character offset and length of the field in `YYYY-MM-DD HH:MM:SS`.
+    pub fn span(self) -> (usize, usize) {
+        match self {
+            Field::Year => (0, 4),
+            Field::Month => (5, 2),
+            Field::Day => (8, 2),
+            Field::Hours => (11, 2),
+            Field::Minutes => (14, 2),
+            Field::Seconds => (17, 2),
+        }
+    }
+}
+
+pub fn is_leap_year(year: u16) -> bool {
+    (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400)
+}
+
+pub fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl DateTime {
+    /// Increments `field`, carrying into the next larger field on overflow.
+    ///
+    /// Returns `false` if the value would leave the supported year range, in
+    /// which case the date is left untouched.
+    pub fn increment(&mut self, field: Field) -> bool {
+        let changed = match field {
+            Field::Year => {
+                if self.year >= MAX_YEAR {
+                    return false;
+                }
+                self.year += 1;
+                true
+            }
+            Field::Month => {
+                if self.month < 12 {
+                    self.month += 1;
+                    true
+                } else if self.increment(Field::Year) {
+                    self.month = 1;
+                    true
+                } else {
+                    false
+                }
+            }
+            Field::Day => {
+                if self.day < days_in_month(self.year, self.month) {
+                    self.day += 1;
+                    true
+                } else if self.increment(Field::Month) {
+                    self.day = 1;
+                    true
+                } else {
+                    false
+                }
+            }
+            Field::Hours | Field::Minutes | Field::Seconds => {
+                let (max, larger) = time_limits(field);
+                if *self.time_field(field) < max {
+                    *self.time_field(field) += 1;
+                    true
+                } else if self.increment(larger) {
+                    *self.time_field(field) = 0;
+                    true
+                } else {
+                    false
+                }
+            }
+        };
+
+        self.clamp_day();
+        changed
+    }
+
+    /// Decrements `field`, borrowing from the next larger field on underflow.
+    ///
+    /// Returns `false` if the value would leave the supported year range, in
+    /// which case the date is left untouched.
+    pub fn decrement(&mut self, field: Field) -> bool {
+        let changed = match field {
+            Field::Year => {
+                if self.year <= MIN_YEAR {
+                    return false;
+                }
+                self.year -= 1;
+                true
+            }
+            Field::Month => {
+                if self.month > 1 {
+                    self.month -= 1;
+                    true
+                } else if self.decrement(Field::Year) {
+                    self.month = 12;
+                    true
+                } else {
+                    false
+                }
+            }
+            Field::Day => {
+                if self.day > 1 {
+                    self.day -= 1;
+                    true
+                } else if self.decrement(Field::Month) {
+                    self.day = days_in_month(self.year, self.month);
+                    true
+                } else {
+                    false
+                }
+            }
+            Field::Hours | Field::Minutes | Field::Seconds => {
+                let (max, larger) = time_limits(field);
+                if *self.time_field(field) > 0 {
+                    *self.time_field(field) -= 1;
+                    true
+                } else if self.decrement(larger) {
+                    *self.time_field(field) = max;
+                    true
+                } else {
+                    false
+                }
+            }
+        };
+
+        self.clamp_day();
+        changed
+    }
+
+    /// Brings an out-of-range value (e.g. from an unset RTC) back into range.
+    pub fn sanitize(&mut self) {
+        self.year = self.year.clamp(MIN_YEAR, MAX_YEAR);
+        self.month = self.month.clamp(1, 12);
+        self.hours = self.hours.min(23);
+        self.minutes = self.minutes.min(59);
+        self.seconds = self.seconds.min(59);
+        self.day = self.day.max(1);
+        self.clamp_day();
+    }
+
+    fn time_field(&mut self, field: Field) -> &mut u8 {
+        match field {
+            Field::Hours => &mut self.hours,
+            Field::Minutes => &mut self.minutes,
+            _ => &mut self.seconds,
+        }
+    }
+
+    fn clamp_day(&mut self) {
+        self.day = self.day.min(days_in_month(self.year, self.month));
+    }
+}
+
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hours, self.minutes, self.seconds
+        )
+    }
+}
+
+fn time_limits(field: Field) -> (u8, Field) {
+    match field {
+        Field::Hours => (23, Field::Day),
+        Field::Minutes => (59, Field::Hours),
+        _ => (59, Field::Minutes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn datetime(year: u16, month: u8, day: u8, hours: u8, minutes: u8, seconds: u8) -> DateTime {
+        DateTime {
+            year,
+            month,
+            day,
+            hours,
+            minutes,
+            seconds,
+        }
+    }
+
+    #[test]
+    fn increment_carries() {
+        let mut value = datetime(2023, 12, 31, 23, 59, 59);
+        assert!(value.increment(Field::Seconds));
+        assert_eq!(value, datetime(2024, 1, 1, 0, 0, 0));
+
+        let mut value = datetime(2024, 2, 28, 10, 0, 0);
+        assert!(value.increment(Field::Day));
+        assert_eq!(value, datetime(2024, 2, 29, 10, 0, 0));
+        assert!(value.increment(Field::Day));
+        assert_eq!(value, datetime(2024, 3, 1, 10, 0, 0));
+    }
+
+    #[test]
+    fn decrement_borrows() {
+        let mut value = datetime(2024, 1, 1, 0, 0, 0);
+        assert!(value.decrement(Field::Seconds));
+        assert_eq!(value, datetime(2023, 12, 31, 23, 59, 59));
+
+        let mut value = datetime(2024, 3, 1, 12, 0, 0);
+        assert!(value.decrement(Field::Day));
+        assert_eq!(value, datetime(2024, 2, 29, 12, 0, 0));
+    }
+
+    #[test]
+    fn year_range_is_kept() {
+        let mut value = datetime(MAX_YEAR, 12, 31, 23, 59, 59);
+        assert!(!value.increment(Field::Seconds));
+        assert_eq!(value, datetime(MAX_YEAR, 12, 31, 23, 59, 59));
+
+        let mut value = datetime(MIN_YEAR, 1, 1, 0, 0, 0);
+        assert!(!value.decrement(Field::Minutes));
+        assert_eq!(value, datetime(MIN_YEAR, 1, 1, 0, 0, 0));
+    }
+
+    #[test]
+    fn day_is_clamped_on_month_change() {
+        let mut value = datetime(2023, 1, 31, 0, 0, 0);
+        assert!(value.increment(Field::Month));
+        assert_eq!((value.month, value.day), (2, 28));
+
+        let mut value = datetime(2023, 3, 31, 0, 0, 0);
+        assert!(value.decrement(Field::Month));
+        assert_eq!((value.month, value.day), (2, 28));
+
+        let mut value = datetime(2023, 5, 31, 0, 0, 0);
+        assert!(value.increment(Field::Month));
+        assert_eq!((value.month, value.day), (6, 30));
+    }
+
+    #[test]
+    fn day_is_clamped_on_year_change() {
+        let mut value = datetime(2024, 2, 29, 0, 0, 0);
+        assert!(value.increment(Field::Year));
+        assert_eq!(value, datetime(2025, 2, 28, 0, 0, 0));
+
+        let mut value = datetime(2024, 2, 29, 0, 0, 0);
+        assert!(value.decrement(Field::Year));
+        assert_eq!(value, datetime(2023, 2, 28, 0, 0, 0));
+    }
+
+    #[test]
+    fn leap_years() {
+        assert!(is_leap_year(2000));
+        assert!(!is_leap_year(2100));
+        assert!(is_leap_year(2024));
+        assert!(!is_leap_year(2023));
+        assert_eq!(days_in_month(2000, 2), 29);
+        assert_eq!(days_in_month(2100, 2), 28);
+        assert_eq!(days_in_month(2024, 2), 29);
+    }
+
+    #[test]
+    fn sanitize_brings_values_into_range() {
+        let mut value = datetime(1999, 13, 0, 24, 60, 61);
+        value.sanitize();
+        assert_eq!(value, datetime(MIN_YEAR, 12, 1, 23, 59, 59));
+
+        let mut value = datetime(2023, 2, 31, 0, 0, 0);
+        value.sanitize();
+        assert_eq!(value.day, 28);
+    }
+}
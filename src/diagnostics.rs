@@ -0,0 +1,258 @@
+use core::fmt::Write;
+
+use embassy_futures::select::{select, Either};
+use embassy_time::Timer;
+use embedded_graphics::{
+    draw_target::{DrawTarget, DrawTargetExt},
+    mono_font::{ascii::FONT_5X7, MonoTextStyle},
+    pixelcolor::BinaryColor,
+    prelude::{OriginDimensions, Point},
+    text::{Baseline, Text},
+    Drawable,
+};
+use heapless::{Deque, String, Vec};
+
+use crate::{
+    app::{App, AppContext},
+    dialog,
+    hal::{Display, Input, Platform},
+    icons,
+    input_handler::{InputEvent, InputSource},
+    log::{self, Record, HISTORY_LEN, LINE_LEN},
+    menu::{Icon, MenuItem, MenuManager},
+    oled, power, status_bar,
+    watchdog::{self, MAX_TASKS},
+};
+
+const PAGES: [MenuItem; 4] = [
+    MenuItem::new("Log").with_key(0),
+    MenuItem::new("Stall a task").with_key(1),
+    MenuItem::new("Power").with_key(2),
+    MenuItem::new("Display").with_key(3),
+];
+
+#[derive(Default)]
+pub struct DiagnosticsApp;
+
+impl App for DiagnosticsApp {
+    const NAME: &'static str = "Diagnostics";
+    const ICON: Option<&'static Icon> = Some(&icons::DIAGNOSTICS);
+
+    async fn run<P: Platform>(&mut self, context: &mut AppContext<'_, P>) {
+        let content_height = status_bar::content_area(context.display.size()).size.height;
+
+        let Some(page) = MenuManager::new(&PAGES, content_height)
+            .choose(context.display, context.input)
+            .await
+        else {
+            return;
+        };
+
+        match page {
+            0 => {
+                LogView::default()
+                    .show(context.display, context.input)
+                    .await;
+            }
+            1 => {
+                stall_task(context.display, context.input).await;
+            }
+            2 => {
+                show_lines(context.display, context.input, power_lines).await;
+            }
+            _ => {
+                show_lines(context.display, context.input, display_lines).await;
+            }
+        }
+    }
+}
+
+/// Lets one of the watched tasks hang to check that the watchdog resets the
+/// device and reports the task afterwards.
+async fn stall_task<D, I>(display: &mut D, input: &mut I) -> Option<()>
+where
+    D: Display,
+    I: Input,
+{
+    let tasks = watchdog::tasks();
+    if tasks.is_empty() {
+        return dialog::message_box(display, input, "Watchdog", "No tasks are watched.").await;
+    }
+
+    let items: Vec<MenuItem, MAX_TASKS> = tasks
+        .iter()
+        .map(|task| MenuItem::new(task.name()))
+        .collect();
+    let content_height = status_bar::content_area(display.size()).size.height;
+    let choice = MenuManager::new(&items, content_height)
+        .choose(display, input)
+        .await?;
+
+    if dialog::confirm(display, input, "Stall the task? The device will reset.").await? {
+        tasks[choice].request_stall();
+    }
+
+    Some(())
+}
+
+fn power_lines() -> [String<32>; 3] {
+    let state = power::state();
+    let mut lines: [String<32>; 3] = Default::default();
+    let _ = write!(lines[0], "State: {}", state.name());
+    let _ = write!(
+        lines[1],
+        "Last input: {} s ago",
+        power::since_activity().as_secs()
+    );
+    let _ = write!(lines[2], "System clock: 1/{}", state.clock_divider());
+
+    lines
+}
+
+fn display_lines() -> [String<32>; 4] {
+    let stats = oled::frame_stats();
+    let mut lines: [String<32>; 4] = Default::default();
+    let _ = write!(lines[0], "Frame time: {} ms", stats.frame_time.as_millis());
+    let _ = write!(lines[1], "Transfer: {} us", stats.transfer_time.as_micros());
+    let _ = write!(lines[2], "Sent: {} bytes", stats.transferred);
+    let _ = write!(lines[3], "Dropped frames: {}", stats.dropped);
+
+    lines
+}
+
+/// Shows the lines from `lines`, refreshed every second, until the button is
+/// pressed.
+async fn show_lines<D, I, const N: usize>(
+    display: &mut D,
+    input: &mut I,
+    lines: fn() -> [String<32>; N],
+) -> Option<()>
+where
+    D: Display,
+    I: Input,
+{
+    let content_area = status_bar::content_area(display.size());
+    let text_style = MonoTextStyle::new(&FONT_5X7, BinaryColor::On);
+    let line_height = (FONT_5X7.character_size.height + 1) as i32;
+
+    loop {
+        display.clear_buffer();
+        status_bar::draw(display).ok()?;
+        let mut content = display.cropped(&content_area);
+        for (row, line) in lines().iter().enumerate() {
+            Text::with_baseline(
+                line,
+                Point::new(0, row as i32 * line_height),
+                text_style,
+                Baseline::Top,
+            )
+            .draw(&mut content)
+            .ok()?;
+        }
+        display.flush().ok()?;
+
+        if let Either::First(InputEvent::Pressed(InputSource::Button)) = select(
+            input.next_event(),
+            Timer::after(status_bar::REFRESH_INTERVAL),
+        )
+        .await
+        {
+            return Some(());
+        }
+    }
+}
+
+/// Scrollable list of the recent log records, newest at the bottom.
+#[derive(Default)]
+struct LogView {
+    records: Deque<Record, HISTORY_LEN>,
+    /// Number of records hidden below the window.
+    scroll: usize,
+}
+
+impl LogView {
+    async fn show<D, I>(&mut self, display: &mut D, input: &mut I) -> Option<()>
+    where
+        D: Display,
+        I: Input,
+    {
+        let content_area = status_bar::content_area(display.size());
+
+        loop {
+            self.records = log::history();
+            self.scroll = self.scroll.min(self.records.len().saturating_sub(1));
+
+            display.clear_buffer();
+            status_bar::draw(display).ok()?;
+            self.draw(&mut display.cropped(&content_area)).ok()?;
+            display.flush().ok()?;
+
+            let event = match select(
+                input.next_event(),
+                Timer::after(status_bar::REFRESH_INTERVAL),
+            )
+            .await
+            {
+                Either::First(event) => event,
+                Either::Second(_) => continue,
+            };
+
+            match event {
+                InputEvent::Pressed(InputSource::Button) => return Some(()),
+                InputEvent::TurnedCW(_) => self.scroll += 1,
+                InputEvent::TurnedCCW(_) => self.scroll = self.scroll.saturating_sub(1),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Drawable for LogView {
+    type Color = BinaryColor;
+    type Output = ();
+
+    fn draw<D>(&self, target: &mut D) -> Result<Self::Output, D::Error>
+    where
+        D: DrawTarget<Color = Self::Color>,
+    {
+        let line_height = FONT_5X7.character_size.height + 1;
+        let lines = (target.bounding_box().size.height / line_height) as usize;
+        let text_style = MonoTextStyle::new(&FONT_5X7, BinaryColor::On);
+
+        target.clear(BinaryColor::Off)?;
+
+        if self.records.is_empty() {
+            Text::with_baseline("No log records", Point::zero(), text_style, Baseline::Top)
+                .draw(target)?;
+            return Ok(());
+        }
+
+        let end = self.records.len() - self.scroll;
+        let start = end.saturating_sub(lines);
+        for (row, record) in self
+            .records
+            .iter()
+            .skip(start)
+            .take(end - start)
+            .enumerate()
+        {
+            let mut line: String<{ LINE_LEN + 24 }> = String::new();
+            let _ = write!(
+                line,
+                "{} {}: {}",
+                record.level.as_char(),
+                record.target,
+                record.text
+            );
+            Text::with_baseline(
+                &line,
+                Point::new(0, (row as u32 * line_height) as i32),
+                text_style,
+                Baseline::Top,
+            )
+            .draw(target)?;
+        }
+
+        Ok(())
+    }
+}
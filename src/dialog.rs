@@ -0,0 +1,359 @@
+use embassy_time::{Duration, Timer};
+use embedded_graphics::{
+    draw_target::{DrawTarget, DrawTargetExt},
+    pixelcolor::BinaryColor,
+    prelude::{Point, Size},
+    primitives::{Primitive, PrimitiveStyleBuilder, Rectangle},
+    text::{Baseline, Text},
+    Drawable,
+};
+use heapless::String;
+
+use crate::{
+    hal::{Display, Input},
+    input_handler::{InputEvent, InputSource},
+    menu::{Menu, MenuItem, MenuStyle},
+    status_bar,
+};
+
+pub const TOAST_DURATION: Duration = Duration::from_millis(1500);
+
+const MAX_LINE_LEN: usize = 32;
+
+/// Asks a yes/no question. Returns `None` if the display could not be updated.
+pub async fn confirm<D, I>(display: &mut D, input: &mut I, question: &str) -> Option<bool>
+where
+    D: Display,
+    I: Input,
+{
+    let style = MenuStyle::default();
+    let font_height = style.font.character_size.height;
+    let content_area = status_bar::content_area(display.size());
+    let width = content_area.size.width;
+    let message_height = content_area.size.height.saturating_sub(2 * font_height);
+
+    let choices = [MenuItem::new("Yes"), MenuItem::new("No")];
+    let choices_area = Rectangle::new(
+        content_area.top_left + Point::new(0, message_height as i32),
+        Size::new(width, 2 * font_height),
+    );
+    let mut menu = Menu::with_style(&choices, choices_area.size.height, style);
+
+    display.clear_buffer();
+    status_bar::draw(display).ok()?;
+    let message_area = Rectangle::new(content_area.top_left, Size::new(width, message_height));
+    draw_wrapped(question, &mut display.cropped(&message_area), &style).ok()?;
+    menu.draw(&mut display.cropped(&choices_area)).ok()?;
+    display.flush().ok()?;
+
+    loop {
+        match input.next_event().await {
+            InputEvent::Pressed(InputSource::Button) => {
+                return Some(menu.selected() == 0);
+            }
+            InputEvent::TurnedCCW(_) => menu.select_item(1),
+            InputEvent::TurnedCW(_) => menu.select_item(0),
+            _ => continue,
+        }
+
+        menu.draw(&mut display.cropped(&choices_area)).ok()?;
+        display.flush().ok()?;
+    }
+}
+
+/// Shows a short message in a framed box on top of the current screen contents
+/// and waits for `duration`. The caller is responsible for redrawing afterwards.
+pub async fn toast<D>(display: &mut D, message: &str, duration: Duration) -> Option<()>
+where
+    D: Display,
+{
+    draw_toast(display, message)?;
+    display.flush().ok()?;
+
+    Timer::after(duration).await;
+
+    Some(())
+}
+
+/// Draws the box of [`toast`] without flushing or waiting, for screens that
+/// keep redrawing underneath it.
+pub fn draw_toast<D>(display: &mut D, message: &str) -> Option<()>
+where
+    D: Display,
+{
+    let style = MenuStyle::default();
+    let font_width = style.font.character_size.width;
+    let font_height = style.font.character_size.height;
+    let display_size = display.size();
+
+    // Too long a message is cut off by the box rather than widening it.
+    let text_width = message.chars().count() as u32 * font_width;
+    let box_size = Size::new(
+        (text_width + font_width).min(display_size.width),
+        font_height + 4,
+    );
+    let box_area = Rectangle::new(
+        Point::new(
+            (display_size.width - box_size.width) as i32 / 2,
+            (display_size.height - box_size.height) as i32 / 2,
+        ),
+        box_size,
+    );
+
+    let frame_style = PrimitiveStyleBuilder::new()
+        .stroke_color(BinaryColor::On)
+        .stroke_width(1)
+        .fill_color(BinaryColor::Off)
+        .build();
+    box_area.into_styled(frame_style).draw(display).ok()?;
+    Text::with_baseline(
+        message,
+        box_area.top_left + Point::new((font_width / 2) as i32, 2),
+        style.normal_text_style,
+        Baseline::Top,
+    )
+    .draw(&mut display.clipped(&box_area))
+    .ok()?;
+
+    Some(())
+}
+
+/// Shows a modal message box with an inverted title bar and waits until the
+/// encoder button or any key is pressed.
+pub async fn message_box<D, I>(
+    display: &mut D,
+    input: &mut I,
+    title: &str,
+    message: &str,
+) -> Option<()>
+where
+    D: Display,
+    I: Input,
+{
+    let style = MenuStyle::default();
+    let font_width = style.font.character_size.width;
+    let font_height = style.font.character_size.height;
+    let content_area = status_bar::content_area(display.size());
+    let width = content_area.size.width;
+
+    display.clear_buffer();
+    status_bar::draw(display).ok()?;
+
+    let title_area = Rectangle::new(content_area.top_left, Size::new(width, font_height));
+    title_area
+        .into_styled(style.selection_style)
+        .draw(display)
+        .ok()?;
+    Text::with_baseline(
+        title,
+        title_area.top_left + Point::new((font_width / 2) as i32, 0),
+        style.inverted_text_style,
+        Baseline::Top,
+    )
+    .draw(display)
+    .ok()?;
+
+    let message_area = Rectangle::new(
+        title_area.top_left + Point::new(0, (font_height + 2) as i32),
+        Size::new(
+            width,
+            content_area.size.height.saturating_sub(font_height + 2),
+        ),
+    );
+    draw_wrapped(message, &mut display.cropped(&message_area), &style).ok()?;
+    display.flush().ok()?;
+
+    loop {
+        if let InputEvent::Pressed(_) = input.next_event().await {
+            return Some(());
+        }
+    }
+}
+
+/// Draws `text` word-wrapped to the width of `target`, one font line at a time.
+/// Newlines start a new line, and words longer than a line are broken where
+/// the line is full. Characters the font lacks, anything but ASCII, show as
+/// `?`.
+pub(crate) fn draw_wrapped<D>(
+    text: &str,
+    target: &mut D,
+    style: &MenuStyle<BinaryColor>,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let font_width = style.font.character_size.width;
+    let font_height = style.font.character_size.height as i32;
+    let bounding_box = target.bounding_box();
+    let max_chars = ((bounding_box.size.width / font_width) as usize).clamp(1, MAX_LINE_LEN);
+
+    target.clear(BinaryColor::Off)?;
+
+    let mut line: String<MAX_LINE_LEN> = String::new();
+    let mut y = 0;
+    let mut draw_line = |line: &mut String<MAX_LINE_LEN>| {
+        Text::with_baseline(
+            line,
+            Point::new(0, y),
+            style.normal_text_style,
+            Baseline::Top,
+        )
+        .draw(target)?;
+        line.clear();
+        y += font_height;
+        Ok(())
+    };
+    for paragraph in text.lines() {
+        for word in paragraph.split_whitespace() {
+            // `line` only holds ASCII, so its length is in characters too.
+            if !line.is_empty() {
+                if line.len() + 1 + word.chars().count() > max_chars {
+                    draw_line(&mut line)?;
+                } else {
+                    let _ = line.push(' ');
+                }
+            }
+            for c in word.chars() {
+                if line.len() == max_chars {
+                    draw_line(&mut line)?;
+                }
+                let _ = line.push(if c.is_ascii() { c } else { '?' });
+            }
+        }
+        draw_line(&mut line)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(all(test, feature = "host"))]
+mod tests {
+    extern crate std;
+
+    use std::{string::String, vec::Vec};
+
+    use embassy_futures::block_on;
+    use embedded_graphics::prelude::OriginDimensions;
+
+    use super::*;
+    use crate::{
+        framebuffer::FrameBuffer,
+        hal::host::{to_ascii, HostDisplay, ScriptedInput},
+    };
+
+    /// Wraps `text` to `chars` characters per line.
+    fn wrapped(text: &str, chars: u32) -> String {
+        let style = MenuStyle::default();
+        let mut frame = FrameBuffer::new();
+        let area = Rectangle::new(
+            Point::zero(),
+            Size::new(chars * style.font.character_size.width, frame.size().height),
+        );
+        draw_wrapped(text, &mut frame.cropped(&area), &style).unwrap();
+
+        to_ascii(&frame)
+    }
+
+    /// `lines` drawn one below the other.
+    fn lines(lines: &[&str]) -> String {
+        let style = MenuStyle::default();
+        let mut frame = FrameBuffer::new();
+        for (row, line) in lines.iter().enumerate() {
+            let y = row as u32 * style.font.character_size.height;
+            Text::with_baseline(
+                line,
+                Point::new(0, y as i32),
+                style.normal_text_style,
+                Baseline::Top,
+            )
+            .draw(&mut frame)
+            .unwrap();
+        }
+
+        to_ascii(&frame)
+    }
+
+    #[test]
+    fn words_wrap_at_whitespace_and_newlines() {
+        assert_eq!(
+            wrapped("one two three\nfour\tfive  sixseven", 10),
+            lines(&["one two", "three", "four five", "sixseven"])
+        );
+    }
+
+    #[test]
+    fn long_word_is_broken_where_the_line_is_full() {
+        assert_eq!(
+            wrapped("a abcdefghijklmnopqrstuvwxyz", 10),
+            lines(&["a", "abcdefghij", "klmnopqrst", "uvwxyz"])
+        );
+    }
+
+    #[test]
+    fn non_ascii_text_is_wrapped() {
+        assert_eq!(
+            wrapped("Ünïcödé wörds ÄÖÜÄÖÜÄÖÜÄÖÜ", 10),
+            lines(&["?n?c?d?", "w?rds", "??????????", "??"])
+        );
+    }
+
+    #[test]
+    fn message_box_shows_the_wrapped_message() {
+        let mut display = HostDisplay::default();
+        let mut input = ScriptedInput::new([InputEvent::Pressed(InputSource::Button)]);
+        let shown = block_on(message_box(&mut display, &mut input, "Panic", "a\nb"));
+        assert_eq!(shown, Some(()));
+        assert!(input.is_empty());
+
+        let style = MenuStyle::default();
+        let content_top = status_bar::content_area(display.flushed.size()).top_left.y;
+        let message_top = (content_top as u32 + style.font.character_size.height + 2) as usize;
+        let message: Vec<_> = to_ascii(&display.flushed)
+            .lines()
+            .skip(message_top)
+            .map(String::from)
+            .collect();
+        let expected = lines(&["a", "b"]);
+        let expected: Vec<_> = expected
+            .lines()
+            .take(message.len())
+            .map(String::from)
+            .collect();
+        assert_eq!(message, expected);
+    }
+
+    #[test]
+    fn long_toast_is_cut_off_at_the_display_edges() {
+        let message = "A message far too long for the panel";
+        let mut display = HostDisplay::default();
+        draw_toast(&mut display, message).unwrap();
+
+        let style = MenuStyle::default();
+        let font_width = style.font.character_size.width;
+        let box_height = style.font.character_size.height + 4;
+        let mut expected = FrameBuffer::new();
+        let box_area = Rectangle::new(
+            Point::new(0, (expected.size().height - box_height) as i32 / 2),
+            Size::new(expected.size().width, box_height),
+        );
+        box_area
+            .into_styled(
+                PrimitiveStyleBuilder::new()
+                    .stroke_color(BinaryColor::On)
+                    .stroke_width(1)
+                    .build(),
+            )
+            .draw(&mut expected)
+            .unwrap();
+        Text::with_baseline(
+            message,
+            box_area.top_left + Point::new((font_width / 2) as i32, 2),
+            style.normal_text_style,
+            Baseline::Top,
+        )
+        .draw(&mut expected.clipped(&box_area))
+        .unwrap();
+
+        assert_eq!(to_ascii(&display.frame), to_ascii(&expected));
+    }
+}
@@ -0,0 +1,165 @@
+//! CHIP-8 mnemonics for the debugger, including the SUPER-CHIP and XO-CHIP
+//! instructions. Addresses and bytes are hex without a prefix, e.g. `JP 2A4`
+//! and `LD V3, 0F`.
+
+use core::fmt::Write;
+
+use heapless::String;
+
+/// Longest result, `DRW VA, VB, F` plus some slack.
+pub const MAX_LEN: usize = 20;
+
+pub fn disassemble(opcode: u16) -> String<MAX_LEN> {
+    let nnn = opcode & 0x0fff;
+    let nn = opcode & 0x00ff;
+    let n = opcode & 0x000f;
+    let x = (opcode >> 8) & 0xf;
+    let y = (opcode >> 4) & 0xf;
+
+    let mut text = String::new();
+    let _ = match (opcode >> 12, x, y, n) {
+        (0x0, 0x0, 0xe, 0x0) => write!(text, "CLS"),
+        (0x0, 0x0, 0xe, 0xe) => write!(text, "RET"),
+        (0x0, 0x0, 0xc, n) => write!(text, "SCD {:X}", n),
+        (0x0, 0x0, 0xd, n) => write!(text, "SCU {:X}", n),
+        (0x0, 0x0, 0xf, 0xb) => write!(text, "SCR"),
+        (0x0, 0x0, 0xf, 0xc) => write!(text, "SCL"),
+        (0x0, 0x0, 0xf, 0xd) => write!(text, "EXIT"),
+        (0x0, 0x0, 0xf, 0xe) => write!(text, "LOW"),
+        (0x0, 0x0, 0xf, 0xf) => write!(text, "HIGH"),
+        (0x0, _, _, _) => write!(text, "SYS {:03X}", nnn),
+        (0x1, _, _, _) => write!(text, "JP {:03X}", nnn),
+        (0x2, _, _, _) => write!(text, "CALL {:03X}", nnn),
+        (0x3, x, _, _) => write!(text, "SE V{:X}, {:02X}", x, nn),
+        (0x4, x, _, _) => write!(text, "SNE V{:X}, {:02X}", x, nn),
+        (0x5, x, y, 0x0) => write!(text, "SE V{:X}, V{:X}", x, y),
+        (0x5, x, y, 0x2) => write!(text, "SAVE V{:X}-V{:X}", x, y),
+        (0x5, x, y, 0x3) => write!(text, "LOAD V{:X}-V{:X}", x, y),
+        (0x6, x, _, _) => write!(text, "LD V{:X}, {:02X}", x, nn),
+        (0x7, x, _, _) => write!(text, "ADD V{:X}, {:02X}", x, nn),
+        (0x8, x, y, n) => match n {
+            0x0 => write!(text, "LD V{:X}, V{:X}", x, y),
+            0x1 => write!(text, "OR V{:X}, V{:X}", x, y),
+            0x2 => write!(text, "AND V{:X}, V{:X}", x, y),
+            0x3 => write!(text, "XOR V{:X}, V{:X}", x, y),
+            0x4 => write!(text, "ADD V{:X}, V{:X}", x, y),
+            0x5 => write!(text, "SUB V{:X}, V{:X}", x, y),
+            0x6 => write!(text, "SHR V{:X}, V{:X}", x, y),
+            0x7 => write!(text, "SUBN V{:X}, V{:X}", x, y),
+            0xe => write!(text, "SHL V{:X}, V{:X}", x, y),
+            _ => unknown(&mut text, opcode),
+        },
+        (0x9, x, y, 0x0) => write!(text, "SNE V{:X}, V{:X}", x, y),
+        (0xa, _, _, _) => write!(text, "LD I, {:03X}", nnn),
+        (0xb, _, _, _) => write!(text, "JP V0, {:03X}", nnn),
+        (0xc, x, _, _) => write!(text, "RND V{:X}, {:02X}", x, nn),
+        (0xd, x, y, n) => write!(text, "DRW V{:X}, V{:X}, {:X}", x, y, n),
+        (0xe, x, _, _) if nn == 0x9e => write!(text, "SKP V{:X}", x),
+        (0xe, x, _, _) if nn == 0xa1 => write!(text, "SKNP V{:X}", x),
+        // The address is in the next word, which the opcode alone lacks.
+        (0xf, 0x0, _, _) if nn == 0x00 => write!(text, "LD I, long"),
+        (0xf, x, _, _) if nn == 0x01 => write!(text, "PLANE {:X}", x),
+        (0xf, 0x0, _, _) if nn == 0x02 => write!(text, "AUDIO"),
+        (0xf, x, _, _) => match nn {
+            0x07 => write!(text, "LD V{:X}, DT", x),
+            0x0a => write!(text, "LD V{:X}, K", x),
+            0x15 => write!(text, "LD DT, V{:X}", x),
+            0x18 => write!(text, "LD ST, V{:X}", x),
+            0x1e => write!(text, "ADD I, V{:X}", x),
+            0x29 => write!(text, "LD F, V{:X}", x),
+            0x30 => write!(text, "LD HF, V{:X}", x),
+            0x33 => write!(text, "LD B, V{:X}", x),
+            0x3a => write!(text, "PITCH V{:X}", x),
+            0x55 => write!(text, "LD [I], V{:X}", x),
+            0x65 => write!(text, "LD V{:X}, [I]", x),
+            0x75 => write!(text, "LD R, V{:X}", x),
+            0x85 => write!(text, "LD V{:X}, R", x),
+            _ => unknown(&mut text, opcode),
+        },
+        _ => unknown(&mut text, opcode),
+    };
+
+    text
+}
+
+/// Data rather than an instruction.
+fn unknown(text: &mut String<MAX_LEN>, opcode: u16) -> core::fmt::Result {
+    write!(text, "DW {:04X}", opcode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CASES: [(u16, &str); 52] = [
+        (0x00e0, "CLS"),
+        (0x00ee, "RET"),
+        (0x00c4, "SCD 4"),
+        (0x00d2, "SCU 2"),
+        (0x00fb, "SCR"),
+        (0x00fc, "SCL"),
+        (0x00fd, "EXIT"),
+        (0x00fe, "LOW"),
+        (0x00ff, "HIGH"),
+        (0x0123, "SYS 123"),
+        (0x12a4, "JP 2A4"),
+        (0x2300, "CALL 300"),
+        (0x3a0f, "SE VA, 0F"),
+        (0x4b10, "SNE VB, 10"),
+        (0x5120, "SE V1, V2"),
+        (0x5122, "SAVE V1-V2"),
+        (0x5123, "LOAD V1-V2"),
+        (0x6a02, "LD VA, 02"),
+        (0x7cff, "ADD VC, FF"),
+        (0x8120, "LD V1, V2"),
+        (0x8121, "OR V1, V2"),
+        (0x8122, "AND V1, V2"),
+        (0x8123, "XOR V1, V2"),
+        (0x8124, "ADD V1, V2"),
+        (0x8125, "SUB V1, V2"),
+        (0x8126, "SHR V1, V2"),
+        (0x8127, "SUBN V1, V2"),
+        (0x812e, "SHL V1, V2"),
+        (0x9de0, "SNE VD, VE"),
+        (0xa2ea, "LD I, 2EA"),
+        (0xb200, "JP V0, 200"),
+        (0xc30f, "RND V3, 0F"),
+        (0xdab5, "DRW VA, VB, 5"),
+        (0xe59e, "SKP V5"),
+        (0xe5a1, "SKNP V5"),
+        (0xf000, "LD I, long"),
+        (0xf201, "PLANE 2"),
+        (0xf002, "AUDIO"),
+        (0xf407, "LD V4, DT"),
+        (0xf40a, "LD V4, K"),
+        (0xf415, "LD DT, V4"),
+        (0xf418, "LD ST, V4"),
+        (0xf41e, "ADD I, V4"),
+        (0xf429, "LD F, V4"),
+        (0xf430, "LD HF, V4"),
+        (0xf433, "LD B, V4"),
+        (0xf43a, "PITCH V4"),
+        (0xf455, "LD [I], V4"),
+        (0xf465, "LD V4, [I]"),
+        (0xf475, "LD R, V4"),
+        (0xf485, "LD V4, R"),
+        (0x8128, "DW 8128"),
+    ];
+
+    const DATA: [u16; 5] = [0x5121, 0x9121, 0xe100, 0xf1ff, 0xffff];
+
+    #[test]
+    fn every_instruction_form_is_disassembled() {
+        for (opcode, expected) in CASES {
+            assert_eq!(disassemble(opcode), expected, "{opcode:04X}");
+        }
+    }
+
+    #[test]
+    fn other_words_are_data() {
+        for opcode in DATA {
+            let text = disassemble(opcode);
+            assert!(text.starts_with("DW "), "{opcode:04X} came out as {text}");
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use core::{convert::Infallible, ops::Range};
+
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    pixelcolor::BinaryColor,
+    prelude::{OriginDimensions, Pixel, Point, Size},
+};
+
+pub const WIDTH: usize = 128;
+pub const HEIGHT: usize = 64;
+pub const PAGES: usize = HEIGHT / 8;
+
+/// An in-memory 128x64 monochrome frame using the SH1106 memory layout: each
+/// byte holds a column of eight vertically stacked pixels of one page.
+#[derive(Clone, PartialEq, Eq)]
+pub struct FrameBuffer {
+    buffer: [u8; WIDTH * PAGES],
+}
+
+impl FrameBuffer {
+    pub const fn new() -> Self {
+        FrameBuffer {
+            buffer: [0; WIDTH * PAGES],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.buffer = [0; WIDTH * PAGES];
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> BinaryColor {
+        if x >= WIDTH || y >= HEIGHT {
+            return BinaryColor::Off;
+        }
+
+        if self.buffer[(y / 8) * WIDTH + x] & (1 << (y % 8)) != 0 {
+            BinaryColor::On
+        } else {
+            BinaryColor::Off
+        }
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: BinaryColor) {
+        if x >= WIDTH || y >= HEIGHT {
+            return;
+        }
+
+        let byte = &mut self.buffer[(y / 8) * WIDTH + x];
+        match color {
+            BinaryColor::On => *byte |= 1 << (y % 8),
+            BinaryColor::Off => *byte &= !(1 << (y % 8)),
+        }
+    }
+
+    pub fn page(&self, page: usize) -> &[u8] {
+        &self.buffer[page * WIDTH..(page + 1) * WIDTH]
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    /// The lit pixels, e.g. to copy the frame onto another draw target.
+    pub fn lit_pixels(&self) -> impl Iterator<Item = Pixel<BinaryColor>> + '_ {
+        (0..HEIGHT).flat_map(move |y| {
+            (0..WIDTH)
+                .filter(move |&x| self.pixel(x, y) == BinaryColor::On)
+                .map(move |x| Pixel(Point::new(x as i32, y as i32), BinaryColor::On))
+        })
+    }
+
+    /// Returns a frame with only the pixels that differ between `self` and
+    /// `other` lit.
+    pub fn diff(&self, other: &FrameBuffer) -> FrameBuffer {
+        let mut diff = FrameBuffer::new();
+        let pairs = self.buffer.iter().zip(other.buffer.iter());
+        for (byte, (a, b)) in diff.buffer.iter_mut().zip(pairs) {
+            *byte = a ^ b;
+        }
+
+        diff
+    }
+
+    /// The span of columns that differ from `previous` in each page, `None`
+    /// for pages that did not change.
+    pub fn dirty_columns(&self, previous: &FrameBuffer) -> [Option<Range<usize>>; PAGES] {
+        core::array::from_fn(|page| {
+            let changed = |(a, b): (&u8, &u8)| a != b;
+            let columns = || self.page(page).iter().zip(previous.page(page));
+            let start = columns().position(changed)?;
+            let end = WIDTH - columns().rev().position(changed)?;
+
+            Some(start..end)
+        })
+    }
+
+    pub fn count_lit(&self) -> usize {
+        self.buffer
+            .iter()
+            .map(|byte| byte.count_ones() as usize)
+            .sum()
+    }
+}
+
+impl Default for FrameBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OriginDimensions for FrameBuffer {
+    fn size(&self) -> Size {
+        Size::new(WIDTH as u32, HEIGHT as u32)
+    }
+}
+
+impl DrawTarget for FrameBuffer {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(Point { x, y }, color) in pixels {
+            if x >= 0 && y >= 0 {
+                self.set_pixel(x as usize, y as usize, color);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_graphics::{
+        prelude::Primitive,
+        primitives::{PrimitiveStyle, Rectangle},
+        Drawable,
+    };
+
+    use super::*;
+    use crate::menu::{Menu, MenuItem, MenuStyle};
+
+    const ITEMS: [MenuItem; 4] = [
+        MenuItem::new("Chip-8 Emulator"),
+        MenuItem::new("Set Date & Time"),
+        MenuItem::new("Settings"),
+        MenuItem::new("About"),
+    ];
+
+    type DirtyColumns = [Option<Range<usize>>; PAGES];
+
+    fn filled(area: Rectangle) -> FrameBuffer {
+        let mut frame = FrameBuffer::new();
+        area.into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+            .draw(&mut frame)
+            .unwrap();
+
+        frame
+    }
+
+    fn menu(selected: usize) -> FrameBuffer {
+        let mut frame = FrameBuffer::new();
+        let mut menu = Menu::with_style(&ITEMS, frame.size().height, MenuStyle::default());
+        menu.select_item(selected);
+        menu.draw(&mut frame).unwrap();
+
+        frame
+    }
+
+    /// Every changed column has to be inside the dirty span of its page, and
+    /// the span has to start and end with a changed column.
+    fn assert_covers_changes(frame: &FrameBuffer, previous: &FrameBuffer) {
+        let dirty = frame.dirty_columns(previous);
+        for (page, columns) in dirty.into_iter().enumerate() {
+            let mut changed = (0..WIDTH)
+                .filter(|&column| frame.page(page)[column] != previous.page(page)[column]);
+            let first = changed.next();
+            let last = changed.next_back().or(first);
+            let expected = first.zip(last).map(|(first, last)| first..last + 1);
+            assert_eq!(columns, expected, "page {page}");
+        }
+    }
+
+    #[test]
+    fn unchanged_frame_sends_nothing() {
+        let blank = FrameBuffer::new();
+        assert_eq!(blank.dirty_columns(&blank), DirtyColumns::default());
+    }
+
+    #[test]
+    fn one_pixel_sends_its_column() {
+        let mut pixel = FrameBuffer::new();
+        pixel.set_pixel(10, 20, BinaryColor::On);
+
+        let mut expected = DirtyColumns::default();
+        expected[2] = Some(10..11);
+        assert_eq!(pixel.dirty_columns(&FrameBuffer::new()), expected);
+    }
+
+    #[test]
+    fn full_frame_sends_everything() {
+        let frame = filled(Rectangle::new(Point::zero(), Size::new(128, 64)));
+        assert_eq!(
+            frame.dirty_columns(&FrameBuffer::new()),
+            core::array::from_fn(|_| Some(0..WIDTH))
+        );
+    }
+
+    #[test]
+    fn change_straddling_pages_sends_both() {
+        let frame = filled(Rectangle::new(Point::new(120, 12), Size::new(8, 8)));
+
+        let mut expected = DirtyColumns::default();
+        expected[1] = Some(120..128);
+        expected[2] = Some(120..128);
+        assert_eq!(frame.dirty_columns(&FrameBuffer::new()), expected);
+    }
+
+    #[test]
+    fn menu_changes_are_covered() {
+        assert_covers_changes(&menu(1), &menu(0));
+        assert_covers_changes(&menu(3), &menu(2));
+        assert_covers_changes(&menu(0), &FrameBuffer::new());
+    }
+}
@@ -0,0 +1,335 @@
+//! Host backend that renders into memory and replays scripted input, for
+//! exercising apps in `cargo host-test`.
+
+extern crate std;
+
+use core::convert::Infallible;
+use std::{collections::VecDeque, string::String, vec::Vec};
+
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    pixelcolor::BinaryColor,
+    prelude::{OriginDimensions, Pixel, Size},
+};
+use smart_leds::RGB8;
+
+use super::{Audio, Clock, Display, Input, Leds, Platform};
+use crate::{
+    checksum::crc32,
+    datetime::DateTime,
+    framebuffer::{FrameBuffer, HEIGHT, WIDTH},
+    input_handler::InputEvent,
+    NEOPIXEL_NUM_LEDS,
+};
+
+pub struct Host;
+
+impl Platform for Host {
+    type Display = HostDisplay;
+    type Input = ScriptedInput;
+    type Leds = HostLeds;
+    type Audio = HostAudio;
+    type Clock = HostClock;
+
+    fn random_seed() -> u64 {
+        0x5eed
+    }
+}
+
+/// Keeps the frame being drawn and the frame that was last flushed separately,
+/// like the SH1106 buffer and panel.
+pub struct HostDisplay {
+    pub frame: FrameBuffer,
+    pub flushed: FrameBuffer,
+    pub flush_count: usize,
+    pub contrast: u8,
+    pub on: bool,
+}
+
+impl Default for HostDisplay {
+    fn default() -> Self {
+        HostDisplay {
+            frame: FrameBuffer::new(),
+            flushed: FrameBuffer::new(),
+            flush_count: 0,
+            contrast: 0x80,
+            on: true,
+        }
+    }
+}
+
+impl OriginDimensions for HostDisplay {
+    fn size(&self) -> Size {
+        self.frame.size()
+    }
+}
+
+impl DrawTarget for HostDisplay {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        self.frame.draw_iter(pixels)
+    }
+}
+
+impl Display for HostDisplay {
+    fn clear_buffer(&mut self) {
+        self.frame.clear();
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.flushed = self.frame.clone();
+        self.flush_count += 1;
+
+        Ok(())
+    }
+
+    fn set_contrast(&mut self, contrast: u8) -> Result<(), Self::Error> {
+        self.contrast = contrast;
+        Ok(())
+    }
+
+    fn set_on(&mut self, on: bool) -> Result<(), Self::Error> {
+        self.on = on;
+        Ok(())
+    }
+}
+
+enum Step {
+    Event(InputEvent),
+    Idle,
+    Stall,
+}
+
+/// Replays a fixed sequence of input events. `next_event` panics once the
+/// script is exhausted, so a test cannot hang waiting for input.
+#[derive(Default)]
+pub struct ScriptedInput {
+    steps: VecDeque<Step>,
+}
+
+impl ScriptedInput {
+    pub fn new(events: impl IntoIterator<Item = InputEvent>) -> Self {
+        let mut input = ScriptedInput::default();
+        for event in events {
+            input.push(event);
+        }
+
+        input
+    }
+
+    pub fn push(&mut self, event: InputEvent) -> &mut Self {
+        self.steps.push_back(Step::Event(event));
+        self
+    }
+
+    /// Makes the next `polls` calls to `try_next_event` see no input, e.g. to
+    /// let the CHIP-8 harness run some frames.
+    pub fn idle(&mut self, polls: usize) -> &mut Self {
+        for _ in 0..polls {
+            self.steps.push_back(Step::Idle);
+        }
+        self
+    }
+
+    /// Makes the next `next_event` never return, so that whatever it is raced
+    /// against wins, e.g. the multi-tap timeout of the text entry.
+    pub fn stall(&mut self) -> &mut Self {
+        self.steps.push_back(Step::Stall);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+}
+
+impl Input for ScriptedInput {
+    async fn next_event(&mut self) -> InputEvent {
+        loop {
+            match self.steps.pop_front() {
+                Some(Step::Event(event)) => return event,
+                Some(Step::Idle) => {}
+                Some(Step::Stall) => core::future::pending().await,
+                None => panic!("input script exhausted"),
+            }
+        }
+    }
+
+    fn try_next_event(&mut self) -> Option<InputEvent> {
+        match self.steps.pop_front()? {
+            Step::Event(event) => Some(event),
+            Step::Idle | Step::Stall => None,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct HostLeds {
+    pub colors: [Option<RGB8>; NEOPIXEL_NUM_LEDS],
+}
+
+impl Leds for HostLeds {
+    fn set(&mut self, index: usize, color: RGB8) {
+        if let Some(led) = self.colors.get_mut(index) {
+            *led = Some(color);
+        }
+    }
+
+    fn release(&mut self, index: usize) {
+        if let Some(led) = self.colors.get_mut(index) {
+            *led = None;
+        }
+    }
+
+    fn release_all(&mut self) {
+        self.colors = [None; NEOPIXEL_NUM_LEDS];
+    }
+}
+
+#[derive(Default)]
+pub struct HostAudio {
+    pub frequency: Option<u32>,
+    pub tones: Vec<u32>,
+}
+
+impl Audio for HostAudio {
+    fn tone(&mut self, frequency: u32) {
+        self.frequency = Some(frequency);
+        self.tones.push(frequency);
+    }
+
+    fn off(&mut self) {
+        self.frequency = None;
+    }
+}
+
+pub struct HostClock {
+    pub datetime: DateTime,
+}
+
+impl Clock for HostClock {
+    fn datetime(&mut self) -> Result<DateTime, ()> {
+        Ok(self.datetime)
+    }
+
+    fn set_datetime(&mut self, datetime: &DateTime) -> Result<(), ()> {
+        self.datetime = *datetime;
+        Ok(())
+    }
+}
+
+/// Renders `frame` as text, one line per row with `#` for lit pixels.
+pub fn to_ascii(frame: &FrameBuffer) -> String {
+    let mut ascii = String::with_capacity((WIDTH + 1) * HEIGHT);
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            ascii.push(match frame.pixel(x, y) {
+                BinaryColor::On => '#',
+                BinaryColor::Off => '.',
+            });
+        }
+        ascii.push('\n');
+    }
+
+    ascii
+}
+
+/// Parses the output of [`to_ascii`]. Returns `None` if `ascii` is not a
+/// 128x64 image.
+pub fn from_ascii(ascii: &str) -> Option<FrameBuffer> {
+    let mut frame = FrameBuffer::new();
+    let mut rows = 0;
+    for (y, line) in ascii.lines().enumerate() {
+        if y >= HEIGHT || line.len() != WIDTH {
+            return None;
+        }
+        for (x, c) in line.chars().enumerate() {
+            match c {
+                '#' => frame.set_pixel(x, y, BinaryColor::On),
+                '.' => {}
+                _ => return None,
+            }
+        }
+        rows += 1;
+    }
+
+    (rows == HEIGHT).then_some(frame)
+}
+
+/// Encodes `frame` as a 1-bit grayscale PNG using uncompressed deflate blocks.
+pub fn to_png(frame: &FrameBuffer) -> Vec<u8> {
+    let mut raw = Vec::with_capacity((1 + WIDTH / 8) * HEIGHT);
+    for y in 0..HEIGHT {
+        // filter type: none
+        raw.push(0);
+        for x in (0..WIDTH).step_by(8) {
+            let mut byte = 0;
+            for bit in 0..8 {
+                if frame.pixel(x + bit, y) == BinaryColor::On {
+                    byte |= 0x80 >> bit;
+                }
+            }
+            raw.push(byte);
+        }
+    }
+
+    let mut png = Vec::new();
+    png.extend_from_slice(b"\x89PNG\r\n\x1a\n");
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(WIDTH as u32).to_be_bytes());
+    header.extend_from_slice(&(HEIGHT as u32).to_be_bytes());
+    // bit depth 1, grayscale, deflate, no filter, no interlace
+    header.extend_from_slice(&[1, 0, 0, 0, 0]);
+    write_chunk(&mut png, b"IHDR", &header);
+
+    write_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+    write_chunk(&mut png, b"IEND", &[]);
+
+    png
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+
+    let crc = crc32(kind.iter().chain(data.iter()).copied());
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut zlib = Vec::with_capacity(data.len() + 16);
+    zlib.extend_from_slice(&[0x78, 0x01]);
+
+    let mut blocks = data.chunks(u16::MAX as usize).peekable();
+    if blocks.peek().is_none() {
+        zlib.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let is_final = blocks.peek().is_none();
+        let len = block.len() as u16;
+        zlib.push(u8::from(is_final));
+        zlib.extend_from_slice(&len.to_le_bytes());
+        zlib.extend_from_slice(&(!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+
+    zlib.extend_from_slice(&adler32(data).to_be_bytes());
+    zlib
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1_u32, 0_u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+
+    (b << 16) | a
+}
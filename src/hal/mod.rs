@@ -0,0 +1,86 @@
+//! Traits for the hardware used by apps, so they can run on the macropad as
+//! well as against the host backend.
+
+use core::fmt::Debug;
+
+use embassy_sync::pubsub::{DynSubscriber, WaitResult};
+use embassy_time::{Duration, Timer};
+use embedded_graphics::{
+    draw_target::DrawTarget, pixelcolor::BinaryColor, prelude::OriginDimensions,
+};
+use smart_leds::RGB8;
+
+use crate::{datetime::DateTime, input_handler::InputEvent, warn};
+
+#[cfg(feature = "host")]
+pub mod host;
+#[cfg(feature = "rp2040")]
+pub mod rp2040;
+
+/// A buffered monochrome display. Drawing only touches the buffer until
+/// `flush` is called.
+pub trait Display: DrawTarget<Color = BinaryColor, Error: Debug> + OriginDimensions {
+    fn clear_buffer(&mut self);
+    fn flush(&mut self) -> Result<(), Self::Error>;
+    fn set_contrast(&mut self, contrast: u8) -> Result<(), Self::Error>;
+    /// Switches the panel off or back on without touching the buffer.
+    fn set_on(&mut self, on: bool) -> Result<(), Self::Error>;
+}
+
+pub trait Input {
+    async fn next_event(&mut self) -> InputEvent;
+    fn try_next_event(&mut self) -> Option<InputEvent>;
+}
+
+pub trait Leds {
+    fn set(&mut self, index: usize, color: RGB8);
+    fn release(&mut self, index: usize);
+    fn release_all(&mut self);
+}
+
+pub trait Audio {
+    fn tone(&mut self, frequency: u32);
+    fn off(&mut self);
+
+    async fn beep(&mut self, frequency: u32, duration: Duration) {
+        self.tone(frequency);
+        Timer::after(duration).await;
+        self.off();
+    }
+}
+
+pub trait Clock {
+    fn datetime(&mut self) -> Result<DateTime, ()>;
+    fn set_datetime(&mut self, datetime: &DateTime) -> Result<(), ()>;
+}
+
+/// Bundles the hardware implementations of one target.
+pub trait Platform {
+    type Display: Display;
+    type Input: Input;
+    type Leds: Leds;
+    type Audio: Audio;
+    type Clock: Clock;
+
+    fn random_seed() -> u64;
+}
+
+impl Input for DynSubscriber<'_, InputEvent> {
+    async fn next_event(&mut self) -> InputEvent {
+        loop {
+            match self.next_message().await {
+                WaitResult::Message(event) => return event,
+                WaitResult::Lagged(missed) => warn!("missed {} input events", missed),
+            }
+        }
+    }
+
+    fn try_next_event(&mut self) -> Option<InputEvent> {
+        loop {
+            match self.try_next_message()? {
+                WaitResult::Message(event) => return Some(event),
+                WaitResult::Lagged(missed) => warn!("missed {} input events", missed),
+            }
+        }
+    }
+}
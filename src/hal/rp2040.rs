@@ -0,0 +1,54 @@
+use core::{fmt::Debug, marker::PhantomData};
+
+use embassy_rp::{clocks::RoscRng, i2c, peripherals::I2C0};
+use embassy_sync::pubsub::DynSubscriber;
+use rand::Rng;
+use sh1106::{interface::DisplayInterface, prelude::GraphicsMode};
+
+use super::{Display, Platform};
+use crate::{
+    audio::Speaker,
+    input_handler::InputEvent,
+    leds::NeoPixels,
+    oled::OledDisplay,
+    rtc::Rtc,
+    screen::{ScreenDisplay, ScreenInput},
+};
+
+/// The macropad hardware, with the display behind the
+/// [`Screen`](crate::screen::Screen) that lives for `'a`.
+pub struct Rp2040<'a>(PhantomData<&'a ()>);
+
+impl<'a> Platform for Rp2040<'a> {
+    type Display = ScreenDisplay<'a, OledDisplay>;
+    type Input = ScreenInput<'a, OledDisplay, DynSubscriber<'static, InputEvent>>;
+    type Leds = NeoPixels;
+    type Audio = Speaker;
+    type Clock = Rtc<'static, I2C0, i2c::Blocking>;
+
+    fn random_seed() -> u64 {
+        RoscRng.gen::<u64>()
+    }
+}
+
+impl<DI> Display for GraphicsMode<DI>
+where
+    DI: DisplayInterface,
+    <DI as DisplayInterface>::Error: Debug,
+{
+    fn clear_buffer(&mut self) {
+        GraphicsMode::clear(self);
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        GraphicsMode::flush(self)
+    }
+
+    fn set_contrast(&mut self, contrast: u8) -> Result<(), Self::Error> {
+        GraphicsMode::set_contrast(self, contrast)
+    }
+
+    fn set_on(&mut self, on: bool) -> Result<(), Self::Error> {
+        GraphicsMode::display_on(self, on)
+    }
+}
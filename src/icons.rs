@@ -0,0 +1,37 @@
+use crate::menu::Icon;
+
+#[rustfmt::skip]
+pub const GAMEPAD: Icon = [
+    0b00000000,
+    0b01111110,
+    0b11011101,
+    0b10001111,
+    0b11011101,
+    0b11111111,
+    0b11100111,
+    0b01000010,
+];
+
+#[rustfmt::skip]
+pub const CLOCK: Icon = [
+    0b00111100,
+    0b01000010,
+    0b10010001,
+    0b10010001,
+    0b10011101,
+    0b10000001,
+    0b01000010,
+    0b00111100,
+];
+
+#[rustfmt::skip]
+pub const DIAGNOSTICS: Icon = [
+    0b01111110,
+    0b01000010,
+    0b01011010,
+    0b01000010,
+    0b01011010,
+    0b01000010,
+    0b01011010,
+    0b01111110,
+];
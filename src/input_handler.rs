@@ -1,20 +1,30 @@
-use embassy_futures::select::{select, Either};
+#[cfg(feature = "rp2040")]
+use embassy_futures::select::{select, select_array, Either};
+#[cfg(feature = "rp2040")]
 use embassy_rp::{
     gpio::{AnyPin, Input, Pull},
     pio::Instance,
 };
-use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, pubsub::Publisher};
+#[cfg(feature = "rp2040")]
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, pubsub::Publisher};
+#[cfg(feature = "rp2040")]
 use embassy_time::{Duration, Timer};
 
-use crate::rotary_io::RotaryIO;
+#[cfg(feature = "rp2040")]
+use crate::{
+    power::{self, PowerState},
+    rotary_io::RotaryIO,
+    trace,
+    watchdog::Watched,
+};
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub enum InputSource {
     Button,
     Key(usize),
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub enum InputEvent {
     Pressed(InputSource),
     Released(InputSource),
@@ -22,8 +32,9 @@ pub enum InputEvent {
     TurnedCCW(i32),
 }
 
-const NUM_KEYS: usize = 12;
+pub const NUM_KEYS: usize = 12;
 
+#[cfg(feature = "rp2040")]
 pub struct InputHandler<'a, P: Instance, const S: usize, const CAP: usize, const SUBS: usize> {
     button_input: Input<'a>,
     button_active: bool,
@@ -31,9 +42,10 @@ pub struct InputHandler<'a, P: Instance, const S: usize, const CAP: usize, const
     key_active: [bool; NUM_KEYS],
     rotary_io: RotaryIO<'a, P, S>,
     encoder_position: i32,
-    publisher: Publisher<'a, ThreadModeRawMutex, InputEvent, CAP, SUBS, 1>,
+    publisher: Publisher<'a, CriticalSectionRawMutex, InputEvent, CAP, SUBS, 1>,
 }
 
+#[cfg(feature = "rp2040")]
 impl<'a, P: Instance, const S: usize, const CAP: usize, const SUBS: usize>
     InputHandler<'a, P, S, CAP, SUBS>
 {
@@ -41,7 +53,7 @@ impl<'a, P: Instance, const S: usize, const CAP: usize, const SUBS: usize>
         button: AnyPin,
         keys: [AnyPin; NUM_KEYS],
         rotary_io: RotaryIO<'a, P, S>,
-        publisher: Publisher<'a, ThreadModeRawMutex, InputEvent, CAP, SUBS, 1>,
+        publisher: Publisher<'a, CriticalSectionRawMutex, InputEvent, CAP, SUBS, 1>,
     ) -> Self {
         let button_input = Input::new(button, Pull::Up);
         let button_active = false;
@@ -62,47 +74,62 @@ impl<'a, P: Instance, const S: usize, const CAP: usize, const SUBS: usize>
         }
     }
 
-    pub async fn run(&mut self) {
+    fn publish(&self, event: InputEvent) {
+        trace!("{:?}", event);
+        power::notify_activity();
+        self.publisher.publish_immediate(event);
+    }
+
+    pub async fn run(&mut self, watched: &Watched) {
         let interval = Duration::from_millis(100);
         loop {
+            watched.check_in();
+
             if !self.button_active && self.button_input.is_low() {
                 self.button_active = true;
-                self.publisher
-                    .publish_immediate(InputEvent::Pressed(InputSource::Button));
+                self.publish(InputEvent::Pressed(InputSource::Button));
             }
             if self.button_active && self.button_input.is_high() {
                 self.button_active = false;
-                self.publisher
-                    .publish_immediate(InputEvent::Released(InputSource::Button));
+                self.publish(InputEvent::Released(InputSource::Button));
             }
 
             for (i, key_input) in self.key_inputs.iter().enumerate() {
                 if !self.key_active[i] && key_input.is_low() {
                     self.key_active[i] = true;
-                    self.publisher
-                        .publish_immediate(InputEvent::Pressed(InputSource::Key(i)));
+                    self.publish(InputEvent::Pressed(InputSource::Key(i)));
                 }
                 if self.key_active[i] && key_input.is_high() {
                     self.key_active[i] = false;
-                    self.publisher
-                        .publish_immediate(InputEvent::Released(InputSource::Key(i)));
+                    self.publish(InputEvent::Released(InputSource::Key(i)));
                 }
             }
 
-            match select(
-                Timer::after(interval),
-                self.rotary_io.wait_position_change(),
-            )
-            .await
-            {
+            let scan = async {
+                if power::state() == PowerState::Active {
+                    Timer::after(interval).await;
+                } else {
+                    // Nothing to poll for, wait for the button or a key to change.
+                    watched.pause();
+                    select(
+                        self.button_input.wait_for_any_edge(),
+                        select_array(
+                            self.key_inputs
+                                .each_mut()
+                                .map(|key_input| key_input.wait_for_any_edge()),
+                        ),
+                    )
+                    .await;
+                }
+            };
+
+            match select(scan, self.rotary_io.wait_position_change()).await {
                 Either::First(_) => {}
                 Either::Second(position) => {
                     if position < self.encoder_position {
-                        self.publisher
-                            .publish_immediate(InputEvent::TurnedCCW(position));
+                        self.publish(InputEvent::TurnedCCW(position));
                     } else {
-                        self.publisher
-                            .publish_immediate(InputEvent::TurnedCW(position));
+                        self.publish(InputEvent::TurnedCW(position));
                     }
                     self.encoder_position = position;
                 }
@@ -0,0 +1,46 @@
+use core::cell::RefCell;
+
+use embassy_sync::blocking_mutex::{raw::CriticalSectionRawMutex, Mutex};
+use smart_leds::RGB8;
+
+use crate::{hal::Leds, NEOPIXEL_NUM_LEDS};
+
+static OVERRIDES: Mutex<CriticalSectionRawMutex, RefCell<[Option<RGB8>; NEOPIXEL_NUM_LEDS]>> =
+    Mutex::new(RefCell::new([None; NEOPIXEL_NUM_LEDS]));
+
+/// Handle for apps to take over individual NeoPixels. LEDs that are not
+/// overridden keep showing the key press animation of `color_fader_task`.
+#[derive(Clone, Copy)]
+pub struct NeoPixels;
+
+impl Leds for NeoPixels {
+    fn set(&mut self, index: usize, color: RGB8) {
+        OVERRIDES.lock(|overrides| {
+            if let Some(led) = overrides.borrow_mut().get_mut(index) {
+                *led = Some(color);
+            }
+        });
+    }
+
+    fn release(&mut self, index: usize) {
+        OVERRIDES.lock(|overrides| {
+            if let Some(led) = overrides.borrow_mut().get_mut(index) {
+                *led = None;
+            }
+        });
+    }
+
+    fn release_all(&mut self) {
+        OVERRIDES.lock(|overrides| *overrides.borrow_mut() = [None; NEOPIXEL_NUM_LEDS]);
+    }
+}
+
+pub fn apply_overrides(data: &mut [RGB8; NEOPIXEL_NUM_LEDS]) {
+    OVERRIDES.lock(|overrides| {
+        for (led, color) in data.iter_mut().zip(overrides.borrow().iter()) {
+            if let Some(color) = color {
+                *led = *color;
+            }
+        }
+    });
+}
@@ -0,0 +1,50 @@
+#![no_std]
+#![allow(async_fn_in_trait)]
+// `Err(())` is all the callers need to know, they show a generic failure.
+#![allow(clippy::result_unit_err)]
+
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, pubsub::PubSubChannel};
+
+use input_handler::InputEvent;
+
+pub mod app;
+#[cfg(feature = "rp2040")]
+pub mod audio;
+pub mod checksum;
+pub mod chip8;
+pub mod clock;
+pub mod datetime;
+pub mod diagnostics;
+pub mod dialog;
+pub mod disassembler;
+pub mod framebuffer;
+pub mod hal;
+pub mod icons;
+pub mod input_handler;
+pub mod leds;
+pub mod log;
+pub mod menu;
+pub mod oled;
+pub mod panic;
+pub mod power;
+pub mod preferences;
+pub mod rom_store;
+#[cfg(feature = "rp2040")]
+pub mod rotary_io;
+#[cfg(feature = "rp2040")]
+pub mod rtc;
+pub mod save_state;
+pub mod screen;
+pub mod status_bar;
+pub mod storage;
+pub mod text_entry;
+#[cfg(feature = "rp2040")]
+pub mod usb_serial;
+pub mod watchdog;
+
+pub const CAP: usize = 8;
+pub const SUBS: usize = 8;
+pub static INPUT_CHANNEL: PubSubChannel<CriticalSectionRawMutex, InputEvent, CAP, SUBS, 1> =
+    PubSubChannel::new();
+
+pub const NEOPIXEL_NUM_LEDS: usize = 12;
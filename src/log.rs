@@ -0,0 +1,204 @@
+//! Logging facade. Messages more verbose than [`MAX_LEVEL`] are compiled out,
+//! the rest are kept in a ring buffer for the diagnostics screen, sent to
+//! defmt/RTT with the `defmt` feature and streamed over USB serial while a
+//! terminal is connected.
+//!
+//! ```ignore
+//! crate::info!("loaded {} byte ROM", rom.len());
+//! ```
+
+#[cfg(feature = "host")]
+extern crate std;
+
+use core::{
+    cell::RefCell,
+    fmt::{self, Write},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use embassy_sync::{
+    blocking_mutex::{raw::CriticalSectionRawMutex, Mutex},
+    pipe::Pipe,
+};
+use embassy_time::Instant;
+use heapless::{Deque, String};
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    pub fn as_char(self) -> char {
+        match self {
+            Level::Error => 'E',
+            Level::Warn => 'W',
+            Level::Info => 'I',
+            Level::Debug => 'D',
+            Level::Trace => 'T',
+        }
+    }
+}
+
+/// The most verbose level that is compiled in, chosen with the `log-*` cargo
+/// features. Defaults to `Info`.
+pub const MAX_LEVEL: Level = if cfg!(feature = "log-trace") {
+    Level::Trace
+} else if cfg!(feature = "log-debug") {
+    Level::Debug
+} else if cfg!(feature = "log-warn") {
+    Level::Warn
+} else if cfg!(feature = "log-error") {
+    Level::Error
+} else {
+    Level::Info
+};
+
+pub const LINE_LEN: usize = 48;
+pub const HISTORY_LEN: usize = 32;
+
+#[derive(Clone)]
+pub struct Record {
+    pub level: Level,
+    pub timestamp: Instant,
+    pub target: &'static str,
+    pub text: String<LINE_LEN>,
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let millis = self.timestamp.as_millis();
+        write!(
+            f,
+            "{:>5}.{:03} {} {}: {}",
+            millis / 1000,
+            millis % 1000,
+            self.level.as_char(),
+            self.target,
+            self.text
+        )
+    }
+}
+
+static HISTORY: Mutex<CriticalSectionRawMutex, RefCell<Deque<Record, HISTORY_LEN>>> =
+    Mutex::new(RefCell::new(Deque::new()));
+
+/// Formatted log lines waiting to be sent over USB serial.
+pub static USB_STREAM: Pipe<CriticalSectionRawMutex, 512> = Pipe::new();
+static USB_STREAM_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Lines are only queued for USB while enabled, so that nothing stale is sent
+/// when a terminal connects.
+pub fn set_usb_stream_enabled(enabled: bool) {
+    USB_STREAM_ENABLED.store(enabled, Ordering::Relaxed);
+    if !enabled {
+        USB_STREAM.clear();
+    }
+}
+
+/// Returns a copy of the most recent records, oldest first.
+pub fn history() -> Deque<Record, HISTORY_LEN> {
+    HISTORY.lock(|history| history.borrow().clone())
+}
+
+#[doc(hidden)]
+pub fn write(level: Level, module_path: &'static str, args: fmt::Arguments) {
+    let target = module_path.rsplit("::").next().unwrap_or(module_path);
+    let mut text: String<LINE_LEN> = String::new();
+    let _ = text.write_fmt(args);
+
+    let record = Record {
+        level,
+        timestamp: Instant::now(),
+        target,
+        text,
+    };
+
+    #[cfg(feature = "defmt")]
+    match level {
+        Level::Error => defmt::error!("{=str}: {=str}", target, record.text.as_str()),
+        Level::Warn => defmt::warn!("{=str}: {=str}", target, record.text.as_str()),
+        Level::Info => defmt::info!("{=str}: {=str}", target, record.text.as_str()),
+        Level::Debug => defmt::debug!("{=str}: {=str}", target, record.text.as_str()),
+        Level::Trace => defmt::trace!("{=str}: {=str}", target, record.text.as_str()),
+    }
+
+    #[cfg(feature = "host")]
+    std::eprintln!("{record}");
+
+    if USB_STREAM_ENABLED.load(Ordering::Relaxed) {
+        let mut line: String<{ LINE_LEN + 32 }> = String::new();
+        let _ = write!(line, "{record}\r\n");
+        // Drop whole lines rather than blocking when the host is not reading.
+        if USB_STREAM.free_capacity() >= line.len() {
+            let _ = USB_STREAM.try_write(line.as_bytes());
+        }
+    }
+
+    // A panic can hit while the history is borrowed, and the panic handler
+    // logs too. Its record is then only sent to the sinks above.
+    HISTORY.lock(|history| {
+        let Ok(mut history) = history.try_borrow_mut() else {
+            return;
+        };
+        if history.is_full() {
+            history.pop_front();
+        }
+        let _ = history.push_back(record);
+    });
+}
+
+#[macro_export]
+macro_rules! log {
+    ($level:expr, $($arg:tt)+) => {
+        if $level <= $crate::log::MAX_LEVEL {
+            $crate::log::write($level, module_path!(), format_args!($($arg)+));
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)+) => { $crate::log!($crate::log::Level::Error, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)+) => { $crate::log!($crate::log::Level::Warn, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)+) => { $crate::log!($crate::log::Level::Info, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)+) => { $crate::log!($crate::log::Level::Debug, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)+) => { $crate::log!($crate::log::Level::Trace, $($arg)+) };
+}
+
+#[cfg(all(test, feature = "host"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_is_dropped_while_the_history_is_borrowed() {
+        HISTORY.lock(|history| {
+            let _history = history.borrow();
+            write(Level::Error, "main", format_args!("panicked in history"));
+        });
+
+        assert!(!history()
+            .iter()
+            .any(|record| record.text == "panicked in history"));
+    }
+}
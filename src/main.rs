@@ -4,51 +4,148 @@
 #![feature(impl_trait_in_assoc_type)]
 #![feature(array_chunks)]
 
-use embassy_executor::Spawner;
+use core::{
+    fmt::Write,
+    panic::PanicInfo,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+#[cfg(feature = "defmt")]
+use defmt_rtt as _;
+use embassy_executor::{InterruptExecutor, Spawner};
+use embassy_futures::block_on;
+use embassy_rp::interrupt;
 use embassy_rp::{
     bind_interrupts,
-    clocks::{clk_sys_freq, RoscRng},
+    clocks::RoscRng,
+    flash::Flash,
     gpio::{AnyPin, Level, Output},
     i2c::{self, I2c},
-    peripherals::{PIO0, PIO1},
+    interrupt::{InterruptExt, Priority},
+    peripherals::{PIO0, PIO1, SPI1, USB},
     pio::{self, Pio},
     pwm::{self, Pwm},
     spi::{self, Blocking, Spi},
+    usb::{self, Driver},
+    watchdog::Watchdog,
 };
-use embassy_sync::{
-    blocking_mutex::raw::ThreadModeRawMutex,
-    pubsub::{PubSubChannel, WaitResult},
-};
-use embassy_time::{Delay, Duration, Timer};
+use embassy_sync::pubsub::WaitResult;
+use embassy_time::{block_for, Delay, Duration, Timer};
 use embedded_graphics::prelude::*;
-use fixed::FixedU16;
-use menu::MenuManager;
-use panic_halt as _;
+use heapless::String;
+use macropad_apps::{
+    app::{self, AppContext, AppEvent},
+    audio::Speaker,
+    dialog, error,
+    hal::{rp2040::Rp2040, Audio, Clock, Display, Input},
+    info,
+    input_handler::{InputEvent, InputHandler, InputSource},
+    leds::{self, NeoPixels},
+    menu::MenuManager,
+    oled::{self, OledDisplay, Sh1106},
+    panic::{self, PanicRecord},
+    power::{self, PowerState},
+    rotary_io,
+    rtc::Rtc,
+    screen::Screen,
+    status_bar, storage, trace, usb_serial, warn,
+    watchdog::{self, ResetReport, Watched},
+    CAP, INPUT_CHANNEL, NEOPIXEL_NUM_LEDS, SUBS,
+};
 use rand::Rng;
-use rtc::Rtc;
 use sh1106::{prelude::*, Builder};
-use smart_leds::hsv::{hsv2rgb, Hsv};
+use smart_leds::{
+    hsv::{hsv2rgb, Hsv},
+    RGB8,
+};
 use ws2812_pio_embassy::Ws2812;
 
-use input_handler::{InputEvent, InputHandler, InputSource};
-
-mod chip8;
-mod input_handler;
-mod menu;
-mod rotary_io;
-mod rtc;
-
 bind_interrupts!(struct Irqs {
     PIO0_IRQ_0 => pio::InterruptHandler<PIO0>;
     PIO1_IRQ_0 => pio::InterruptHandler<PIO1>;
+    USBCTRL_IRQ => usb::InterruptHandler<USB>;
 });
 
-const CAP: usize = 8;
-const SUBS: usize = 8;
-static INPUT_CHANNEL: PubSubChannel<ThreadModeRawMutex, InputEvent, CAP, SUBS, 1> =
-    PubSubChannel::new();
+static INPUT_HANDLER_WATCH: Watched = Watched::new("input", Duration::from_secs(1));
+static COLOR_FADER_WATCH: Watched = Watched::new("leds", Duration::from_secs(1));
+
+/// Runs the watchdog supervisor above the thread mode executor, so it can
+/// notice a task that never yields.
+static SUPERVISOR_EXECUTOR: InterruptExecutor = InterruptExecutor::new();
+
+#[interrupt]
+unsafe fn SWI_IRQ_1() {
+    SUPERVISOR_EXECUTOR.on_interrupt()
+}
+
+/// Reset the device this long after a panic, or never if `None`.
+const PANIC_REBOOT_AFTER: Option<Duration> = Some(Duration::from_secs(10));
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    static PANICKING: AtomicBool = AtomicBool::new(false);
+
+    // thumbv6m has no atomic swap, but with interrupts disabled nothing can
+    // get between the load and the store.
+    cortex_m::interrupt::disable();
+    if PANICKING.load(Ordering::Relaxed) {
+        // Panicked while reporting a panic, there is nothing left to try.
+        loop {
+            cortex_m::asm::wfe();
+        }
+    }
+    PANICKING.store(true, Ordering::Relaxed);
+
+    let record = PanicRecord::new(info);
+    panic::store(&record);
+    error!("{}", record);
+
+    // Whoever owned the hardware is never going to run again.
+    let peripherals = unsafe { embassy_rp::Peripherals::steal() };
 
-const NEOPIXEL_NUM_LEDS: usize = 12;
+    // The supervisor can no longer feed it, and the reboot is up to us.
+    Watchdog::new(peripherals.WATCHDOG).stop();
+
+    let mut display_config = spi::Config::default();
+    display_config.frequency = 10_000_000;
+    let spi: Spi<'_, _, Blocking> = Spi::new_blocking(
+        peripherals.SPI1,
+        peripherals.PIN_26,
+        peripherals.PIN_27,
+        peripherals.PIN_28,
+        display_config,
+    );
+    let oled_cs = Output::new(peripherals.PIN_22, Level::Low);
+    let mut oled_reset = Output::new(peripherals.PIN_23, Level::Low);
+    let oled_dc = Output::new(peripherals.PIN_24, Level::Low);
+    let mut display: GraphicsMode<_> = Builder::new().connect_spi(spi, oled_dc, oled_cs).into();
+    if display.reset(&mut oled_reset, &mut Delay).is_ok() && display.init().is_ok() {
+        let _ = panic::draw(&record, "PANIC", &mut display);
+        let _ = display.flush();
+    }
+
+    let Pio {
+        mut common, sm0, ..
+    } = Pio::new(peripherals.PIO1, Irqs);
+    let mut ws2812 = Ws2812::new(&mut common, sm0, peripherals.DMA_CH0, peripherals.PIN_19);
+    let red = [RGB8::new(255, 0, 0); NEOPIXEL_NUM_LEDS];
+    let off = [RGB8::default(); NEOPIXEL_NUM_LEDS];
+
+    let flash_interval = Duration::from_millis(500);
+    let mut elapsed = Duration::from_secs(0);
+    loop {
+        for data in [&red, &off] {
+            // Interrupts are off, `block_on` polls the DMA transfer until it is done.
+            block_on(ws2812.write(data));
+            block_for(flash_interval);
+            elapsed += flash_interval;
+        }
+
+        if PANIC_REBOOT_AFTER.is_some_and(|timeout| elapsed >= timeout) {
+            cortex_m::peripheral::SCB::sys_reset();
+        }
+    }
+}
 
 #[embassy_executor::task]
 async fn blinker_task(mut led: Output<'static>, interval: Duration) {
@@ -78,21 +175,45 @@ async fn blinker_task(mut led: Output<'static>, interval: Duration) {
 #[embassy_executor::task]
 async fn color_fader_task(mut ws2812: Ws2812<'static, PIO1, 0, NEOPIXEL_NUM_LEDS>) {
     let mut input_subscriber = INPUT_CHANNEL.subscriber().unwrap();
+    let mut app_events = app::APP_EVENTS.subscriber().unwrap();
 
     let mut hues_and_values = [(0, 0); 12];
+    // The key press animation stops while an app is suspended, and starts
+    // over whenever an app starts or quits.
+    let mut animating = true;
 
     loop {
+        COLOR_FADER_WATCH.check_in();
+
+        if power::state() != PowerState::Active {
+            ws2812.write(&[RGB8::default(); NEOPIXEL_NUM_LEDS]).await;
+            hues_and_values = [(0, 0); 12];
+            COLOR_FADER_WATCH.pause();
+            power::wait_until_active().await;
+            continue;
+        }
+
+        while let Some(event) = app_events.try_next_message_pure() {
+            hues_and_values = [(0, 0); 12];
+            animating = !matches!(event, AppEvent::Suspended(_));
+        }
+
         if let Some(WaitResult::Message(InputEvent::Pressed(InputSource::Key(key)))) =
             input_subscriber.try_next_message()
         {
-            hues_and_values[key] = (RoscRng.gen::<u8>(), 255);
+            if animating {
+                let hue = RoscRng.gen::<u8>();
+                trace!("key {} hue {}", key, hue);
+                hues_and_values[key] = (hue, 255);
+            }
         }
 
-        let data = hues_and_values.map(|(hue, val)| {
+        let mut data = hues_and_values.map(|(hue, val)| {
             let hsv = Hsv { hue, sat: 255, val };
 
             hsv2rgb(hsv)
         });
+        leds::apply_overrides(&mut data);
 
         ws2812.write(&data).await;
 
@@ -108,14 +229,50 @@ async fn color_fader_task(mut ws2812: Ws2812<'static, PIO1, 0, NEOPIXEL_NUM_LEDS
     }
 }
 
+#[embassy_executor::task]
+async fn display_task(panel: Sh1106<'static, SPI1>) {
+    oled::run(panel).await;
+}
+
+#[embassy_executor::task]
+async fn power_task() {
+    power::run().await;
+}
+
+#[embassy_executor::task]
+async fn usb_task(driver: Driver<'static, USB>) {
+    usb_serial::run(driver).await;
+}
+
 #[embassy_executor::task]
 async fn input_handler_task(mut input_handler: InputHandler<'static, PIO0, 0, CAP, SUBS>) {
-    input_handler.run().await;
+    input_handler.run(&INPUT_HANDLER_WATCH).await;
+}
+
+#[embassy_executor::task]
+async fn supervisor_task(watchdog: Watchdog) {
+    watchdog::supervise(watchdog).await;
 }
 
 #[embassy_executor::main]
 async fn main(spawner: Spawner) {
     let peripherals = embassy_rp::init(Default::default());
+    info!("starting");
+
+    storage::init(Flash::new_blocking(peripherals.FLASH));
+    let driver = Driver::new(peripherals.USB, Irqs);
+    spawner.spawn(usb_task(driver)).unwrap();
+    spawner.spawn(power_task()).unwrap();
+
+    let mut hardware_watchdog = Watchdog::new(peripherals.WATCHDOG);
+    let reset_report = watchdog::take_reset_report(&mut hardware_watchdog);
+    watchdog::register(&INPUT_HANDLER_WATCH);
+    watchdog::register(&COLOR_FADER_WATCH);
+    interrupt::SWI_IRQ_1.set_priority(Priority::P2);
+    SUPERVISOR_EXECUTOR
+        .start(interrupt::SWI_IRQ_1)
+        .spawn(supervisor_task(hardware_watchdog))
+        .unwrap();
 
     let scl = peripherals.PIN_21;
     let sda = peripherals.PIN_20;
@@ -160,97 +317,99 @@ async fn main(spawner: Spawner) {
         .spawn(blinker_task(led, Duration::from_millis(300)))
         .unwrap();
 
-    let mut speaker_enable = Output::new(peripherals.PIN_14, Level::High);
-    let mut pwm_config: pwm::Config = Default::default();
-    pwm_config.divider = FixedU16::from_num(40);
-    pwm_config.top = (clk_sys_freq() as f64 / f64::from(pwm_config.divider) / 440.0) as u16;
-    pwm_config.compare_a = pwm_config.top / 2;
-    let mut pwm = Pwm::new_output_a(peripherals.PWM_SLICE0, peripherals.PIN_16, pwm_config);
-    Timer::after(Duration::from_secs(2)).await;
-    speaker_enable.set_low();
+    let speaker_enable = Output::new(peripherals.PIN_14, Level::Low);
+    let pwm = Pwm::new_output_a(
+        peripherals.PWM_SLICE0,
+        peripherals.PIN_16,
+        pwm::Config::default(),
+    );
+    let mut speaker = Speaker::new(pwm, speaker_enable);
+    speaker.beep(440, Duration::from_secs(2)).await;
 
     let sclk = peripherals.PIN_26;
     let mosi = peripherals.PIN_27;
-    let miso = peripherals.PIN_28;
     let mut display_config = spi::Config::default();
     display_config.frequency = 10_000_000;
-    let spi: Spi<'_, _, Blocking> =
-        Spi::new_blocking(peripherals.SPI1, sclk, mosi, miso, display_config);
+    let spi = Spi::new_txonly(
+        peripherals.SPI1,
+        sclk,
+        mosi,
+        peripherals.DMA_CH1,
+        display_config,
+    );
 
     let oled_cs = Output::new(peripherals.PIN_22, Level::Low);
     let mut oled_reset = Output::new(peripherals.PIN_23, Level::Low);
     let oled_dc = Output::new(peripherals.PIN_24, Level::Low);
 
-    let mut display: GraphicsMode<_> = Builder::new().connect_spi(spi, oled_dc, oled_cs).into();
+    let mut panel = Sh1106::new(spi, oled_dc, oled_cs);
+    panel.init(&mut oled_reset).await.unwrap();
+    spawner.spawn(display_task(panel)).unwrap();
+
+    let screen = Screen::new(OledDisplay::default());
+    let mut screen_display = screen.display();
+    let mut input = screen.input(INPUT_CHANNEL.dyn_subscriber().unwrap());
+    let mut context: AppContext<Rp2040<_>> = AppContext {
+        display: &mut screen_display,
+        input: &mut input,
+        leds: &mut NeoPixels,
+        audio: &mut speaker,
+        clock: &mut rtc,
+    };
+
+    if let Some(record) = panic::take_last() {
+        warn!("restarted after panic: {}", record);
+        if panic::draw(&record, "Last panic", context.display).is_ok() {
+            flush(context.display);
+        }
+        while !matches!(context.input.next_event().await, InputEvent::Pressed(_)) {}
+    }
 
-    display.reset(&mut oled_reset, &mut Delay).unwrap();
-    display.init().unwrap();
-    display.flush().unwrap();
-
-    while let Some(choice) = MenuManager::new(
-        &[
-            "Chip-8 Emulator",
-            "Set Date & Time",
-            "Hello world!",
-            "Hello Marc!",
-            "Test Item 4",
-            "Test Item 5",
-        ],
-        display.size().height,
-    )
-    .choose(&mut display)
-    .await
-    {
-        display.clear();
-        display.flush().unwrap();
-        match choice {
-            0 => {
-                let choice = MenuManager::new(&["Pong", "Blinky"], display.size().height)
-                    .choose(&mut display)
-                    .await;
-
-                if let Some(choice) = choice {
-                    display.clear();
-                    display.flush().unwrap();
-
-                    let mut chip8 = chip8::Chip8Harness::new();
-                    match choice {
-                        0 => {
-                            chip8
-                                .run(
-                                    include_bytes!("../chip8-rs/games/PONG"),
-                                    [1, 0, 12, 4, 0, 13, 0, 0, 0, 0, 0, 0],
-                                    &mut display,
-                                )
-                                .await
-                                .unwrap();
-                        }
-                        1 => {
-                            chip8
-                                .run(
-                                    include_bytes!("../chip8-rs/games/BLINKY"),
-                                    [0, 3, 0, 7, 6, 8, 0, 0, 0, 0, 0, 0],
-                                    &mut display,
-                                )
-                                .await
-                                .unwrap();
-                        }
-                        _ => unreachable!(),
-                    };
-                }
-            }
-            1 => {
-                let display_height = display.size().height;
-                rtc.set_interactive(&mut display, display_height).await;
-            }
-            _ => {}
+    if let Some(report) = reset_report {
+        let stalled = match report {
+            ResetReport::TaskStalled(index) => watchdog::tasks()
+                .get(index)
+                .map_or("a task", |task| task.name()),
+            ResetReport::TimedOut => "the supervisor",
+        };
+        warn!("reset by the watchdog, {} stalled", stalled);
+
+        let mut message: String<48> = String::new();
+        let _ = write!(message, "Reset after {} stopped responding.", stalled);
+        dialog::message_box(context.display, context.input, "Watchdog", &message).await;
+    }
+
+    loop {
+        if let Ok(datetime) = context.clock.datetime() {
+            status_bar::sync_clock(&datetime);
         }
+        status_bar::set_mode("Menu");
+
+        let content_height = status_bar::content_area(context.display.size()).size.height;
+        let Some(choice) = MenuManager::new(&app::MENU, content_height)
+            .choose(context.display, context.input)
+            .await
+        else {
+            break;
+        };
+
+        context.display.clear_buffer();
+        flush(context.display);
+        app::launch(choice, &mut context).await;
     }
 
-    display.clear();
-    display.flush().unwrap();
+    context.display.clear_buffer();
+    flush(context.display);
 
     loop {
         Timer::after(Duration::from_secs(1)).await;
     }
 }
+
+/// Flushes `display`, logging a failure rather than panicking, as the next
+/// flush sends the whole buffer again anyway.
+fn flush<D: Display>(display: &mut D) {
+    if let Err(e) = display.flush() {
+        error!("flushing the display failed: {:?}", e);
+    }
+}
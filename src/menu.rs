@@ -1,49 +1,71 @@
-use embassy_futures::select;
-use embassy_rp::usb::In;
-use embassy_sync::{
-    blocking_mutex::raw::RawMutex,
-    pubsub::{DynSubscriber, Subscriber, WaitResult},
-};
+use core::fmt::Write;
+
+use embassy_futures::select::{select, Either};
+use embassy_time::Timer;
 use embedded_graphics::{
-    draw_target::DrawTarget,
+    draw_target::{DrawTarget, DrawTargetExt},
     mono_font::{ascii::FONT_6X10, MonoFont, MonoTextStyle, MonoTextStyleBuilder},
     pixelcolor::{BinaryColor, PixelColor},
-    prelude::{Point, Size},
+    prelude::{Pixel, Point, Size},
     primitives::{Primitive, PrimitiveStyle, PrimitiveStyleBuilder, Rectangle},
-    text::{Baseline, Text},
+    text::{Alignment, Baseline, Text, TextStyleBuilder},
     Drawable,
 };
-use sh1106::{interface::DisplayInterface, prelude::GraphicsMode};
+use heapless::String;
 
 use crate::{
+    hal::{Display, Input},
     input_handler::{InputEvent, InputSource},
-    INPUT_CHANNEL,
+    status_bar,
 };
 
-pub struct Menu<'a, C: PixelColor> {
-    items: &'a [&'a str],
-    selected: usize,
-    caption_offset: usize,
-    window_start: usize,
-    window_len: usize,
-    font: &'a MonoFont<'a>,
-    normal_text_style: MonoTextStyle<'a, C>,
-    inverted_text_style: MonoTextStyle<'a, C>,
-    selection_style: PrimitiveStyle<C>,
-    clear_style: PrimitiveStyle<C>,
+pub const ICON_SIZE: u32 = 8;
+
+/// An 8x8 monochrome bitmap, one byte per row with the most significant bit on
+/// the left.
+pub type Icon = [u8; ICON_SIZE as usize];
+
+#[derive(Clone, Copy)]
+pub struct MenuItem<'a> {
+    pub label: &'a str,
+    pub icon: Option<&'a Icon>,
+    pub key: Option<usize>,
 }
 
-impl<'a, C: PixelColor> Menu<'a, C> {
-    pub fn new(
-        items: &'a [&str],
-        height: u32,
-        font: &'a MonoFont,
-        bg_color: C,
-        fg_color: C,
-    ) -> Self {
-        let font_height = font.character_size.height;
-        let window_len = (height / font_height) as usize;
+impl<'a> MenuItem<'a> {
+    pub const fn new(label: &'a str) -> Self {
+        MenuItem {
+            label,
+            icon: None,
+            key: None,
+        }
+    }
+
+    pub const fn with_icon(mut self, icon: &'a Icon) -> Self {
+        self.icon = Some(icon);
+        self
+    }
 
+    /// Lets the physical key with index `key` jump to this item.
+    pub const fn with_key(mut self, key: usize) -> Self {
+        self.key = Some(key);
+        self
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct MenuStyle<'a, C: PixelColor> {
+    pub font: &'a MonoFont<'a>,
+    pub bg_color: C,
+    pub fg_color: C,
+    pub normal_text_style: MonoTextStyle<'a, C>,
+    pub inverted_text_style: MonoTextStyle<'a, C>,
+    pub selection_style: PrimitiveStyle<C>,
+    pub clear_style: PrimitiveStyle<C>,
+}
+
+impl<'a, C: PixelColor> MenuStyle<'a, C> {
+    pub fn new(font: &'a MonoFont, bg_color: C, fg_color: C) -> Self {
         let normal_text_style = MonoTextStyleBuilder::new()
             .font(font)
             .text_color(fg_color)
@@ -66,17 +88,55 @@ impl<'a, C: PixelColor> Menu<'a, C> {
             .fill_color(bg_color)
             .build();
 
+        MenuStyle {
+            font,
+            bg_color,
+            fg_color,
+            normal_text_style,
+            inverted_text_style,
+            selection_style,
+            clear_style,
+        }
+    }
+}
+
+impl Default for MenuStyle<'static, BinaryColor> {
+    fn default() -> Self {
+        MenuStyle::new(&FONT_6X10, BinaryColor::Off, BinaryColor::On)
+    }
+}
+
+pub struct Menu<'a, C: PixelColor> {
+    items: &'a [MenuItem<'a>],
+    selected: usize,
+    caption_offset: usize,
+    window_start: usize,
+    window_len: usize,
+    style: MenuStyle<'a, C>,
+}
+
+impl<'a, C: PixelColor> Menu<'a, C> {
+    pub fn new(
+        items: &'a [MenuItem<'a>],
+        height: u32,
+        font: &'a MonoFont,
+        bg_color: C,
+        fg_color: C,
+    ) -> Self {
+        Self::with_style(items, height, MenuStyle::new(font, bg_color, fg_color))
+    }
+
+    pub fn with_style(items: &'a [MenuItem<'a>], height: u32, style: MenuStyle<'a, C>) -> Self {
+        let font_height = style.font.character_size.height;
+        let window_len = (height / font_height) as usize;
+
         Menu {
             items,
             selected: 0,
             caption_offset: 0,
             window_start: 0,
             window_len,
-            font,
-            normal_text_style,
-            inverted_text_style,
-            selection_style,
-            clear_style,
+            style,
         }
     }
 
@@ -97,6 +157,14 @@ impl<'a, C: PixelColor> Menu<'a, C> {
         }
     }
 
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn item_for_key(&self, key: usize) -> Option<usize> {
+        self.items.iter().position(|item| item.key == Some(key))
+    }
+
     pub fn scroll_item(&mut self, offset: usize) {
         self.caption_offset = offset;
     }
@@ -110,42 +178,65 @@ impl<C: PixelColor> Drawable for Menu<'_, C> {
     where
         D: DrawTarget<Color = Self::Color>,
     {
-        let font_width = self.font.character_size.width;
-        let font_height = self.font.character_size.height;
+        let font_width = self.style.font.character_size.width;
+        let font_height = self.style.font.character_size.height;
         let target_width = target.bounding_box().columns().len() as u32;
 
         target
             .bounding_box()
-            .into_styled(self.clear_style)
+            .into_styled(self.style.clear_style)
             .draw(target)?;
 
+        let has_icons = self.items.iter().any(|item| item.icon.is_some());
+        let label_offset = if has_icons {
+            font_width / 2 + ICON_SIZE + font_width / 2
+        } else {
+            font_width / 2
+        };
+
         for i in self.window_start..(self.window_start + self.window_len) {
             if i >= self.items.len() {
                 break;
             }
 
+            let item = &self.items[i];
             let rectangle_position =
                 Point::new(0, (font_height * (i - self.window_start) as u32) as i32);
             let rectangle_size = Size::new(target_width, font_height);
-            let text_position = rectangle_position + Point::new((font_width / 2) as i32, 0);
-            if i == self.selected {
+            let text_position = rectangle_position + Point::new(label_offset as i32, 0);
+            let (text_style, icon_color) = if i == self.selected {
                 Rectangle::new(rectangle_position, rectangle_size)
-                    .into_styled(self.selection_style)
+                    .into_styled(self.style.selection_style)
                     .draw(target)?;
 
-                Text::with_baseline(
-                    self.items[i],
-                    text_position,
-                    self.inverted_text_style,
-                    Baseline::Top,
-                )
-                .draw(target)?;
+                (self.style.inverted_text_style, self.style.bg_color)
             } else {
-                Text::with_baseline(
-                    self.items[i],
-                    text_position,
-                    self.normal_text_style,
-                    Baseline::Top,
+                (self.style.normal_text_style, self.style.fg_color)
+            };
+
+            Text::with_baseline(item.label, text_position, text_style, Baseline::Top)
+                .draw(target)?;
+
+            if let Some(icon) = item.icon {
+                let icon_position = rectangle_position
+                    + Point::new(
+                        (font_width / 2) as i32,
+                        (font_height.saturating_sub(ICON_SIZE) / 2) as i32,
+                    );
+                draw_icon(icon, icon_position, icon_color, target)?;
+            }
+
+            if let Some(key) = item.key {
+                let mut hint: String<3> = String::new();
+                let _ = write!(hint, "{}", key + 1);
+                Text::with_text_style(
+                    &hint,
+                    rectangle_position + Point::new((target_width - font_width / 2) as i32, 0),
+                    text_style,
+                    TextStyleBuilder::new()
+                        .alignment(Alignment::Right)
+                        .baseline(Baseline::Top)
+                        .build(),
                 )
                 .draw(target)?;
             }
@@ -155,71 +246,183 @@ impl<C: PixelColor> Drawable for Menu<'_, C> {
     }
 }
 
-pub struct MenuManager<'m, 'i> {
+fn draw_icon<C, D>(icon: &Icon, position: Point, color: C, target: &mut D) -> Result<(), D::Error>
+where
+    C: PixelColor,
+    D: DrawTarget<Color = C>,
+{
+    let pixels = icon.iter().enumerate().flat_map(|(y, row)| {
+        (0..ICON_SIZE as usize)
+            .filter(move |x| row & (0x80 >> x) != 0)
+            .map(move |x| Pixel(position + Point::new(x as i32, y as i32), color))
+    });
+
+    target.draw_iter(pixels)
+}
+
+pub struct MenuManager<'m> {
     menu: Menu<'m, BinaryColor>,
-    input_subscriber: DynSubscriber<'i, InputEvent>,
 }
 
-impl<'m, 'i> MenuManager<'m, 'i> {
-    pub fn new(menu_items: &'m [&str], display_height: u32) -> Self {
-        let menu = Menu::new(
-            menu_items,
-            display_height,
-            &FONT_6X10,
-            BinaryColor::Off,
-            BinaryColor::On,
-        );
-
-        let input_subscriber = INPUT_CHANNEL.dyn_subscriber().unwrap();
-
-        MenuManager {
-            menu,
-            input_subscriber,
-        }
+impl<'m> MenuManager<'m> {
+    pub fn new(menu_items: &'m [MenuItem<'m>], display_height: u32) -> Self {
+        let menu = Menu::with_style(menu_items, display_height, MenuStyle::default());
+
+        MenuManager { menu }
     }
 
     pub fn select_item(&mut self, item: usize) {
         self.menu.select_item(item);
     }
 
-    pub async fn choose<DI>(&mut self, display: &mut GraphicsMode<DI>) -> Option<usize>
+    pub async fn choose<D, I>(&mut self, display: &mut D, input: &mut I) -> Option<usize>
     where
-        DI: DisplayInterface,
+        D: Display,
+        I: Input,
     {
-        display.clear();
-        self.menu.draw(display).ok()?;
+        let content_area = status_bar::content_area(display.size());
+
+        display.clear_buffer();
+        status_bar::draw(display).ok()?;
+        self.menu.draw(&mut display.cropped(&content_area)).ok()?;
         display.flush().ok()?;
 
         loop {
-            let wait_result = self.input_subscriber.next_message().await;
-
-            if let WaitResult::Message(msg) = wait_result {
-                match msg {
-                    InputEvent::Pressed(InputSource::Key(key)) => {
-                        self.menu.select_item(key);
-                        self.menu.draw(display).ok()?;
-                        display.flush().ok()?;
-                    }
-                    InputEvent::Pressed(InputSource::Button) => {
-                        return Some(self.menu.selected);
-                    }
-                    InputEvent::TurnedCCW(_) => {
-                        if self.menu.selected < self.menu.items.len() - 1 {
-                            self.menu.select_item(self.menu.selected + 1);
-                            self.menu.draw(display).ok()?;
-                            display.flush().ok()?;
-                        }
-                    }
-                    InputEvent::TurnedCW(_) => {
-                        if self.menu.selected > 0 {
-                            self.menu.select_item(self.menu.selected - 1);
-                            self.menu.draw(display).ok()?;
-                            display.flush().ok()?;
-                        }
-                    }
-                    _ => {}
+            let msg = match select(
+                input.next_event(),
+                Timer::after(status_bar::REFRESH_INTERVAL),
+            )
+            .await
+            {
+                Either::First(msg) => msg,
+                Either::Second(_) => {
+                    status_bar::draw(display).ok()?;
+                    display.flush().ok()?;
+                    continue;
+                }
+            };
+
+            match msg {
+                InputEvent::Pressed(InputSource::Key(key)) => {
+                    let Some(item) = self.menu.item_for_key(key) else {
+                        continue;
+                    };
+                    self.menu.select_item(item);
+                    self.menu.draw(&mut display.cropped(&content_area)).ok()?;
+                    display.flush().ok()?;
                 }
+                InputEvent::Pressed(InputSource::Button) => {
+                    return Some(self.menu.selected);
+                }
+                InputEvent::TurnedCCW(_) if self.menu.selected < self.menu.items.len() - 1 => {
+                    self.menu.select_item(self.menu.selected + 1);
+                    self.menu.draw(&mut display.cropped(&content_area)).ok()?;
+                    display.flush().ok()?;
+                }
+                InputEvent::TurnedCW(_) if self.menu.selected > 0 => {
+                    self.menu.select_item(self.menu.selected - 1);
+                    self.menu.draw(&mut display.cropped(&content_area)).ok()?;
+                    display.flush().ok()?;
+                }
+                _ => {}
             }
         }
     }
 }
+
+#[cfg(all(test, feature = "host"))]
+mod tests {
+    extern crate std;
+
+    use std::{string::String, vec::Vec};
+
+    use embassy_futures::block_on;
+    use embedded_graphics::prelude::OriginDimensions;
+
+    use super::*;
+    use crate::{
+        framebuffer::FrameBuffer,
+        hal::host::{to_ascii, HostDisplay, ScriptedInput},
+    };
+
+    const ITEMS: [MenuItem; 3] = [
+        MenuItem::new("Alpha"),
+        MenuItem::new("Beta"),
+        MenuItem::new("Gamma").with_key(4),
+    ];
+
+    /// The rows of `frame` below the status bar, where the menu goes.
+    fn content(frame: &FrameBuffer) -> String {
+        let top = status_bar::content_area(frame.size()).top_left.y as usize;
+        to_ascii(frame)
+            .lines()
+            .skip(top)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn expected(selected: usize) -> String {
+        let mut frame = FrameBuffer::new();
+        let content_area = status_bar::content_area(frame.size());
+        let mut menu = Menu::with_style(&ITEMS, content_area.size.height, MenuStyle::default());
+        menu.select_item(selected);
+        menu.draw(&mut frame.cropped(&content_area)).unwrap();
+
+        content(&frame)
+    }
+
+    fn choose(events: impl IntoIterator<Item = InputEvent>) -> (Option<usize>, HostDisplay) {
+        let mut display = HostDisplay::default();
+        let mut input = ScriptedInput::new(events);
+        let height = status_bar::content_area(display.size()).size.height;
+        let choice = block_on(MenuManager::new(&ITEMS, height).choose(&mut display, &mut input));
+        assert!(input.is_empty());
+
+        (choice, display)
+    }
+
+    #[test]
+    fn encoder_moves_the_selection() {
+        let (choice, display) = choose([
+            InputEvent::TurnedCCW(1),
+            InputEvent::TurnedCCW(1),
+            InputEvent::TurnedCW(1),
+            InputEvent::Pressed(InputSource::Button),
+        ]);
+
+        assert_eq!(choice, Some(1));
+        assert_eq!(content(&display.flushed), expected(1));
+    }
+
+    #[test]
+    fn selection_stops_at_the_ends() {
+        let (choice, display) = choose([
+            InputEvent::TurnedCW(1),
+            InputEvent::Pressed(InputSource::Button),
+        ]);
+        assert_eq!(choice, Some(0));
+        assert_eq!(content(&display.flushed), expected(0));
+
+        let (choice, display) = choose([
+            InputEvent::TurnedCCW(1),
+            InputEvent::TurnedCCW(1),
+            InputEvent::TurnedCCW(1),
+            InputEvent::Pressed(InputSource::Button),
+        ]);
+        assert_eq!(choice, Some(2));
+        assert_eq!(content(&display.flushed), expected(2));
+    }
+
+    #[test]
+    fn key_selects_its_item() {
+        let (choice, display) = choose([
+            InputEvent::Pressed(InputSource::Key(0)),
+            InputEvent::Pressed(InputSource::Key(4)),
+            InputEvent::Pressed(InputSource::Button),
+        ]);
+
+        assert_eq!(choice, Some(2));
+        assert_eq!(content(&display.flushed), expected(2));
+        assert_ne!(expected(2), expected(0));
+    }
+}
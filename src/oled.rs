@@ -0,0 +1,301 @@
+//! Display path that keeps SPI transfers off the app's task. Apps draw into an
+//! [`OledDisplay`], whose `flush` only hands a copy of the frame over. The
+//! display task sends it to the SH1106 with DMA, so other tasks keep running
+//! during the transfer. Frames flushed faster than the panel takes them are
+//! dropped, only the newest one is sent. Of that, only the columns that changed
+//! since the previous frame are transmitted.
+
+#[cfg(feature = "rp2040")]
+use core::ops::Range;
+use core::{cell::RefCell, convert::Infallible};
+
+#[cfg(feature = "rp2040")]
+use embassy_rp::{
+    gpio::Output,
+    spi::{self, Async, Spi},
+};
+use embassy_sync::{
+    blocking_mutex::{raw::CriticalSectionRawMutex, Mutex},
+    signal::Signal,
+};
+use embassy_time::Duration;
+#[cfg(feature = "rp2040")]
+use embassy_time::{Instant, Timer};
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    pixelcolor::BinaryColor,
+    prelude::{OriginDimensions, Pixel, Size},
+};
+
+#[cfg(feature = "rp2040")]
+use crate::{
+    error,
+    framebuffer::{PAGES, WIDTH},
+};
+use crate::{framebuffer::FrameBuffer, hal::Display};
+
+/// The SH1106 has 132 columns, the 128 visible ones start at column 2.
+#[cfg(feature = "rp2040")]
+const COLUMN_OFFSET: u8 = 2;
+
+#[derive(Clone, Copy, Default)]
+pub struct FrameStats {
+    /// Time between the last two frames that reached the panel.
+    pub frame_time: Duration,
+    /// How long sending the last frame took.
+    pub transfer_time: Duration,
+    /// Bytes of pixel data sent for the last frame.
+    pub transferred: usize,
+    /// Frames replaced by a newer one before they were sent.
+    pub dropped: u32,
+}
+
+#[derive(Default)]
+struct Pending {
+    frame: Option<FrameBuffer>,
+    contrast: Option<u8>,
+    on: Option<bool>,
+}
+
+static PENDING: Mutex<CriticalSectionRawMutex, RefCell<Pending>> =
+    Mutex::new(RefCell::new(Pending {
+        frame: None,
+        contrast: None,
+        on: None,
+    }));
+static PENDING_CHANGED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+static STATS: Mutex<CriticalSectionRawMutex, RefCell<FrameStats>> =
+    Mutex::new(RefCell::new(FrameStats {
+        frame_time: Duration::from_ticks(0),
+        transfer_time: Duration::from_ticks(0),
+        transferred: 0,
+        dropped: 0,
+    }));
+
+pub fn frame_stats() -> FrameStats {
+    STATS.lock(|stats| *stats.borrow())
+}
+
+fn update_pending(f: impl FnOnce(&mut Pending)) {
+    PENDING.lock(|pending| f(&mut pending.borrow_mut()));
+    PENDING_CHANGED.signal(());
+}
+
+/// The display handle for apps. Draws into its own buffer, see the module
+/// documentation.
+#[derive(Default)]
+pub struct OledDisplay {
+    frame: FrameBuffer,
+}
+
+impl OriginDimensions for OledDisplay {
+    fn size(&self) -> Size {
+        self.frame.size()
+    }
+}
+
+impl DrawTarget for OledDisplay {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        self.frame.draw_iter(pixels)
+    }
+}
+
+impl Display for OledDisplay {
+    fn clear_buffer(&mut self) {
+        self.frame.clear();
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        update_pending(|pending| {
+            if pending.frame.replace(self.frame.clone()).is_some() {
+                STATS.lock(|stats| stats.borrow_mut().dropped += 1);
+            }
+        });
+
+        Ok(())
+    }
+
+    fn set_contrast(&mut self, contrast: u8) -> Result<(), Self::Error> {
+        update_pending(|pending| pending.contrast = Some(contrast));
+
+        Ok(())
+    }
+
+    fn set_on(&mut self, on: bool) -> Result<(), Self::Error> {
+        update_pending(|pending| pending.on = Some(on));
+
+        Ok(())
+    }
+}
+
+/// SH1106 driver on a write-only SPI bus with DMA.
+#[cfg(feature = "rp2040")]
+pub struct Sh1106<'d, T: spi::Instance> {
+    spi: Spi<'d, T, Async>,
+    dc: Output<'d>,
+    cs: Output<'d>,
+}
+
+#[cfg(feature = "rp2040")]
+impl<'d, T: spi::Instance> Sh1106<'d, T> {
+    pub fn new(spi: Spi<'d, T, Async>, dc: Output<'d>, cs: Output<'d>) -> Self {
+        Sh1106 { spi, dc, cs }
+    }
+
+    /// Resets and configures the panel, leaving it on with a blank frame.
+    pub async fn init(&mut self, reset: &mut Output<'_>) -> Result<(), spi::Error> {
+        reset.set_high();
+        Timer::after_millis(1).await;
+        reset.set_low();
+        Timer::after_millis(10).await;
+        reset.set_high();
+
+        #[rustfmt::skip]
+        self.command(&[
+            0xae,       // display off
+            0xd5, 0x80, // clock divider
+            0xa8, 0x3f, // multiplex ratio, 64 rows
+            0xd3, 0x00, // display offset
+            0x40,       // start line 0
+            0xad, 0x8b, // DC-DC converter on
+            0xa1,       // segment remap
+            0xc8,       // COM scan direction reversed
+            0xda, 0x12, // COM pins configuration
+            0x81, 0x80, // contrast
+            0xd9, 0x22, // pre-charge period
+            0xdb, 0x35, // VCOM deselect level
+            0xa4,       // show the RAM contents
+            0xa6,       // not inverted
+        ])
+        .await?;
+        self.write_frame(&FrameBuffer::new()).await?;
+        self.set_on(true).await
+    }
+
+    pub async fn write_frame(&mut self, frame: &FrameBuffer) -> Result<(), spi::Error> {
+        for page in 0..PAGES {
+            self.write_columns(frame, page, 0..WIDTH).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes only what changed since `previous`, which must be what the panel
+    /// shows. Returns the number of bytes of pixel data sent.
+    pub async fn write_changes(
+        &mut self,
+        frame: &FrameBuffer,
+        previous: &FrameBuffer,
+    ) -> Result<usize, spi::Error> {
+        let mut transferred = 0;
+        for (page, columns) in frame.dirty_columns(previous).into_iter().enumerate() {
+            if let Some(columns) = columns {
+                transferred += columns.len();
+                self.write_columns(frame, page, columns).await?;
+            }
+        }
+
+        Ok(transferred)
+    }
+
+    async fn write_columns(
+        &mut self,
+        frame: &FrameBuffer,
+        page: usize,
+        columns: Range<usize>,
+    ) -> Result<(), spi::Error> {
+        let column = COLUMN_OFFSET + columns.start as u8;
+        self.command(&[0xb0 | page as u8, column & 0x0f, 0x10 | column >> 4])
+            .await?;
+        self.data(&frame.page(page)[columns]).await
+    }
+
+    pub async fn set_contrast(&mut self, contrast: u8) -> Result<(), spi::Error> {
+        self.command(&[0x81, contrast]).await
+    }
+
+    pub async fn set_on(&mut self, on: bool) -> Result<(), spi::Error> {
+        self.command(&[if on { 0xaf } else { 0xae }]).await
+    }
+
+    async fn command(&mut self, bytes: &[u8]) -> Result<(), spi::Error> {
+        self.dc.set_low();
+        self.write(bytes).await
+    }
+
+    async fn data(&mut self, bytes: &[u8]) -> Result<(), spi::Error> {
+        self.dc.set_high();
+        self.write(bytes).await
+    }
+
+    async fn write(&mut self, bytes: &[u8]) -> Result<(), spi::Error> {
+        self.cs.set_low();
+        let result = self.spi.write(bytes).await;
+        self.cs.set_high();
+        result
+    }
+}
+
+/// Sends whatever the [`OledDisplay`] handed over to the panel, which has to
+/// show a blank frame when this starts, as after `init`.
+#[cfg(feature = "rp2040")]
+pub async fn run<T: spi::Instance>(mut panel: Sh1106<'_, T>) -> ! {
+    let mut last_frame: Option<Instant> = None;
+    // What the panel shows, unknown after a failed transfer.
+    let mut shown = Some(FrameBuffer::new());
+
+    loop {
+        PENDING_CHANGED.wait().await;
+        let pending = PENDING.lock(|pending| core::mem::take(&mut *pending.borrow_mut()));
+
+        let mut result = Ok(());
+        if let Some(on) = pending.on {
+            result = result.and(panel.set_on(on).await);
+        }
+        if let Some(contrast) = pending.contrast {
+            result = result.and(panel.set_contrast(contrast).await);
+        }
+        if let Some(frame) = pending.frame {
+            let started = Instant::now();
+            let written = match &shown {
+                Some(shown) => panel.write_changes(&frame, shown).await,
+                None => panel
+                    .write_frame(&frame)
+                    .await
+                    .map(|()| frame.as_bytes().len()),
+            };
+            let mut transferred = 0;
+            match written {
+                Ok(bytes) => {
+                    transferred = bytes;
+                    shown = Some(frame);
+                }
+                Err(e) => {
+                    result = Err(e);
+                    shown = None;
+                }
+            }
+
+            let now = Instant::now();
+            STATS.lock(|stats| {
+                let mut stats = stats.borrow_mut();
+                stats.transfer_time = now - started;
+                stats.transferred = transferred;
+                if let Some(last_frame) = last_frame {
+                    stats.frame_time = now - last_frame;
+                }
+            });
+            last_frame = Some(now);
+        }
+
+        if let Err(e) = result {
+            error!("display update failed: {:?}", e);
+        }
+    }
+}
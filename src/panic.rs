@@ -0,0 +1,181 @@
+//! A fixed-size record of the last panic, kept in RAM that is not initialized
+//! on startup so it can be shown again after the device reset itself.
+
+use core::{
+    fmt::{self, Write},
+    mem::MaybeUninit,
+    panic::PanicInfo,
+    ptr::{addr_of, addr_of_mut},
+};
+
+use embedded_graphics::{
+    draw_target::{DrawTarget, DrawTargetExt},
+    mono_font::ascii::FONT_5X7,
+    pixelcolor::BinaryColor,
+    prelude::{Point, Primitive, Size},
+    primitives::Rectangle,
+    text::{Baseline, Text},
+    Drawable,
+};
+use heapless::String;
+
+use crate::{dialog, menu::MenuStyle};
+
+pub const FILE_LEN: usize = 24;
+pub const MESSAGE_LEN: usize = 160;
+
+const MAGIC: u32 = 0x5041_4e43;
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct PanicRecord {
+    magic: u32,
+    line: u32,
+    file_len: u8,
+    message_len: u8,
+    file: [u8; FILE_LEN],
+    message: [u8; MESSAGE_LEN],
+}
+
+#[cfg_attr(feature = "rp2040", link_section = ".uninit.LAST_PANIC")]
+static mut LAST_PANIC: MaybeUninit<PanicRecord> = MaybeUninit::uninit();
+
+impl PanicRecord {
+    pub fn new(info: &PanicInfo) -> Self {
+        let mut record = PanicRecord {
+            magic: MAGIC,
+            line: 0,
+            file_len: 0,
+            message_len: 0,
+            file: [0; FILE_LEN],
+            message: [0; MESSAGE_LEN],
+        };
+
+        if let Some(location) = info.location() {
+            // Keep the end of the path, it is the part that identifies the file.
+            let file = location.file();
+            let mut start = file.len().saturating_sub(FILE_LEN);
+            while !file.is_char_boundary(start) {
+                start += 1;
+            }
+            let file = &file.as_bytes()[start..];
+            record.file[..file.len()].copy_from_slice(file);
+            record.file_len = file.len() as u8;
+            record.line = location.line();
+        }
+
+        let mut writer = Truncating {
+            buffer: &mut record.message,
+            len: 0,
+        };
+        let _ = write!(writer, "{}", info.message());
+        record.message_len = writer.len as u8;
+
+        record
+    }
+
+    pub fn file(&self) -> &str {
+        core::str::from_utf8(&self.file[..self.file_len as usize]).unwrap_or("")
+    }
+
+    pub fn line(&self) -> u32 {
+        self.line
+    }
+
+    pub fn message(&self) -> &str {
+        core::str::from_utf8(&self.message[..self.message_len as usize]).unwrap_or("")
+    }
+
+    fn is_valid(&self) -> bool {
+        self.magic == MAGIC
+            && self.file_len as usize <= FILE_LEN
+            && self.message_len as usize <= MESSAGE_LEN
+            && core::str::from_utf8(&self.file[..self.file_len as usize]).is_ok()
+            && core::str::from_utf8(&self.message[..self.message_len as usize]).is_ok()
+    }
+}
+
+impl fmt::Display for PanicRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}:{}", self.message(), self.file(), self.line)
+    }
+}
+
+/// Writes into a fixed buffer and silently drops whatever does not fit.
+struct Truncating<'a> {
+    buffer: &'a mut [u8],
+    len: usize,
+}
+
+impl Write for Truncating<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            let mut encoded = [0; 4];
+            let encoded = c.encode_utf8(&mut encoded).as_bytes();
+            if self.len + encoded.len() > self.buffer.len() {
+                break;
+            }
+            self.buffer[self.len..self.len + encoded.len()].copy_from_slice(encoded);
+            self.len += encoded.len();
+        }
+
+        Ok(())
+    }
+}
+
+/// Keeps `record` for [`take_last`] after the next soft reset.
+pub fn store(record: &PanicRecord) {
+    unsafe { addr_of_mut!(LAST_PANIC).write_volatile(MaybeUninit::new(*record)) }
+}
+
+/// Returns the record stored before the last reset, if any, and forgets it.
+pub fn take_last() -> Option<PanicRecord> {
+    // Any bit pattern is a valid `PanicRecord`, so reading memory that was
+    // never written after power-on is fine. The magic tells the two apart.
+    let record = unsafe { addr_of!(LAST_PANIC).read_volatile().assume_init() };
+    let mut cleared = record;
+    cleared.magic = 0;
+    store(&cleared);
+
+    record.is_valid().then_some(record)
+}
+
+/// Draws a full screen report of `record` below `title`, with the small font so
+/// that most messages fit.
+pub fn draw<D>(record: &PanicRecord, title: &str, target: &mut D) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let style = MenuStyle::new(&FONT_5X7, BinaryColor::Off, BinaryColor::On);
+    let font_height = style.font.character_size.height;
+    let size = target.bounding_box().size;
+
+    target.clear(BinaryColor::Off)?;
+
+    let title_area = Rectangle::new(Point::zero(), Size::new(size.width, font_height + 1));
+    title_area.into_styled(style.selection_style).draw(target)?;
+    Text::with_baseline(
+        title,
+        Point::new(1, 1),
+        style.inverted_text_style,
+        Baseline::Top,
+    )
+    .draw(target)?;
+
+    let mut location: String<{ FILE_LEN + 12 }> = String::new();
+    let _ = write!(location, "{}:{}", record.file(), record.line);
+    Text::with_baseline(
+        &location,
+        Point::new(0, font_height as i32 + 2),
+        style.normal_text_style,
+        Baseline::Top,
+    )
+    .draw(target)?;
+
+    let message_top = 2 * font_height + 4;
+    let message_area = Rectangle::new(
+        Point::new(0, message_top as i32),
+        Size::new(size.width, size.height.saturating_sub(message_top)),
+    );
+    dialog::draw_wrapped(record.message(), &mut target.cropped(&message_area), &style)
+}
@@ -0,0 +1,146 @@
+//! System power management. Input keeps the system [`PowerState::Active`].
+//! Without it the system goes [`PowerState::Idle`], where the LED animation
+//! and input polling stop and only GPIO edges wake the input handler, and
+//! later [`PowerState::Sleep`], which also slows down the system clock.
+//!
+//! embassy only sets the clocks up at boot, so the `clk_sys` divider is written
+//! directly and [`clk_sys_freq`] has to be used instead of embassy's. Dividers
+//! set up before sleeping keep their value: the encoder PIO samples and SPI
+//! runs at half rate, and a tone that is still playing drops an octave. The
+//! LEDs are not driven while asleep, and input restores the full clock before
+//! it is published.
+//!
+//! Dormant mode is not used: it stops the timer the watchdog supervisor runs
+//! on, and drops the USB connection.
+
+use core::cell::Cell;
+
+use embassy_futures::select::select;
+use embassy_sync::{
+    blocking_mutex::{raw::CriticalSectionRawMutex, Mutex},
+    signal::Signal,
+    watch::Watch,
+};
+use embassy_time::{Duration, Instant, Timer};
+
+use crate::{info, screen};
+
+/// The LEDs and polling go idle together with the display dimming.
+pub const IDLE_AFTER: Duration = screen::DIM_AFTER;
+/// Sleep once the display is off, nothing needs the full clock then.
+pub const SLEEP_AFTER: Duration = screen::OFF_AFTER;
+
+/// `clk_sys` divider while asleep. Keeps `clk_sys` above the 48 MHz USB clock.
+pub const SLEEP_CLOCK_DIVIDER: u32 = 2;
+
+const MAX_WAITERS: usize = 4;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PowerState {
+    Active,
+    Idle,
+    Sleep,
+}
+
+impl PowerState {
+    /// The state the system should be in after `idle` without input.
+    pub fn after(idle: Duration) -> Self {
+        if idle >= SLEEP_AFTER {
+            PowerState::Sleep
+        } else if idle >= IDLE_AFTER {
+            PowerState::Idle
+        } else {
+            PowerState::Active
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PowerState::Active => "Active",
+            PowerState::Idle => "Idle",
+            PowerState::Sleep => "Sleep",
+        }
+    }
+
+    pub fn clock_divider(self) -> u32 {
+        match self {
+            PowerState::Sleep => SLEEP_CLOCK_DIVIDER,
+            _ => 1,
+        }
+    }
+}
+
+static STATE: Watch<CriticalSectionRawMutex, PowerState, MAX_WAITERS> =
+    Watch::new_with(PowerState::Active);
+static LAST_ACTIVITY: Mutex<CriticalSectionRawMutex, Cell<Instant>> =
+    Mutex::new(Cell::new(Instant::from_ticks(0)));
+static ACTIVITY: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Records user input, waking the system up if needed.
+pub fn notify_activity() {
+    LAST_ACTIVITY.lock(|last_activity| last_activity.set(Instant::now()));
+    // Whatever reacts to the input may need the full clock, don't leave it to
+    // `run` to get scheduled first.
+    if state() == PowerState::Sleep {
+        set_clock_divider(PowerState::Active.clock_divider());
+    }
+    ACTIVITY.signal(());
+}
+
+pub fn state() -> PowerState {
+    STATE.try_get().unwrap_or(PowerState::Active)
+}
+
+pub fn since_activity() -> Duration {
+    LAST_ACTIVITY
+        .lock(|last_activity| last_activity.get())
+        .elapsed()
+}
+
+/// Returns once the system is active, immediately if it already is.
+pub async fn wait_until_active() {
+    // Only fails with more than `MAX_WAITERS` waiting, don't sleep through it.
+    let Some(mut receiver) = STATE.receiver() else {
+        return;
+    };
+    receiver.get_and(|state| *state == PowerState::Active).await;
+}
+
+/// Moves between the power states. Only this task changes the state.
+pub async fn run() -> ! {
+    loop {
+        let last_activity = LAST_ACTIVITY.lock(|last_activity| last_activity.get());
+        let new_state = PowerState::after(last_activity.elapsed());
+        if new_state != state() {
+            enter(new_state);
+        }
+
+        let deadline = match new_state {
+            PowerState::Active => last_activity + IDLE_AFTER,
+            PowerState::Idle => last_activity + SLEEP_AFTER,
+            PowerState::Sleep => Instant::MAX,
+        };
+        select(ACTIVITY.wait(), Timer::at(deadline)).await;
+    }
+}
+
+fn enter(state: PowerState) {
+    info!("power state {}", state.name());
+    set_clock_divider(state.clock_divider());
+    STATE.sender().send(state);
+}
+
+#[cfg_attr(not(feature = "rp2040"), allow(unused_variables))]
+fn set_clock_divider(divider: u32) {
+    #[cfg(feature = "rp2040")]
+    embassy_rp::pac::CLOCKS
+        .clk_sys_div()
+        .write(|w| w.set_int(divider));
+}
+
+/// `clk_sys` as it runs now, with the sleep divider applied.
+#[cfg(feature = "rp2040")]
+pub fn clk_sys_freq() -> u32 {
+    let divider = embassy_rp::pac::CLOCKS.clk_sys_div().read().int();
+    embassy_rp::clocks::clk_sys_freq() / divider.max(1)
+}
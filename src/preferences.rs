@@ -0,0 +1,147 @@
+//! Per-ROM settings kept in the preferences sector of [`storage`]: a header
+//! followed by one entry per ROM, keyed by a hash of its name.
+//!
+//! ```text
+//! "PREF" version:u8 count:u16
+//! name hash:u32 speed:u16    (count times)
+//! ```
+//!
+//! All numbers are little endian. A sector with another magic or version reads
+//! as empty.
+
+use crate::storage::{self, PREFERENCES_SECTOR, SECTOR_SIZE};
+
+const MAGIC: [u8; 4] = *b"PREF";
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = 7;
+const ENTRY_LEN: usize = 6;
+pub const MAX_ENTRIES: usize = (SECTOR_SIZE - HEADER_LEN) / ENTRY_LEN;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Entry {
+    pub rom: u32,
+    pub speed: u16,
+}
+
+/// FNV-1a, so that entries have a fixed size whatever the ROM names.
+pub fn rom_hash(name: &str) -> u32 {
+    name.bytes().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// The entries stored in `bytes`, the contents of the preferences sector.
+pub fn decode(bytes: &[u8]) -> impl Iterator<Item = Entry> + '_ {
+    let count = match bytes.get(..HEADER_LEN) {
+        Some(header) if header[..4] == MAGIC && header[4] == VERSION => {
+            u16::from_le_bytes([header[5], header[6]]) as usize
+        }
+        _ => 0,
+    };
+
+    bytes
+        .get(HEADER_LEN..)
+        .unwrap_or_default()
+        .chunks_exact(ENTRY_LEN)
+        .take(count.min(MAX_ENTRIES))
+        .map(|entry| Entry {
+            rom: u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]),
+            speed: u16::from_le_bytes([entry[4], entry[5]]),
+        })
+}
+
+/// Writes `entries` into `sector`, dropping any beyond `MAX_ENTRIES`.
+pub fn encode(sector: &mut [u8; SECTOR_SIZE], entries: impl Iterator<Item = Entry>) {
+    sector.fill(0xff);
+    let mut count = 0;
+    for (entry, bytes) in entries.zip(sector[HEADER_LEN..].chunks_exact_mut(ENTRY_LEN)) {
+        bytes[..4].copy_from_slice(&entry.rom.to_le_bytes());
+        bytes[4..].copy_from_slice(&entry.speed.to_le_bytes());
+        count += 1;
+    }
+
+    sector[..4].copy_from_slice(&MAGIC);
+    sector[4] = VERSION;
+    sector[5..7].copy_from_slice(&(count as u16).to_le_bytes());
+}
+
+/// The instructions per frame last chosen for the ROM `name`.
+pub fn speed(name: &str) -> Option<u32> {
+    let rom = rom_hash(name);
+    decode(storage::sector(PREFERENCES_SECTOR))
+        .find(|entry| entry.rom == rom)
+        .map(|entry| entry.speed as u32)
+}
+
+/// Remembers `speed` for the ROM `name`. This rewrites the whole sector, so
+/// only call it when the speed actually changed.
+pub fn set_speed(name: &str, speed: u32) -> Result<(), ()> {
+    let entry = Entry {
+        rom: rom_hash(name),
+        speed: speed.min(u16::MAX as u32) as u16,
+    };
+    // The latest entry goes first, so when the sector is full the ROM changed
+    // longest ago is the one dropped.
+    let others = decode(storage::sector(PREFERENCES_SECTOR)).filter(|other| other.rom != entry.rom);
+    let mut sector = [0; SECTOR_SIZE];
+    encode(&mut sector, core::iter::once(entry).chain(others));
+
+    storage::write(PREFERENCES_SECTOR, &sector)
+}
+
+/// Moves the settings of the ROM `from` over to the ROM `to`.
+pub fn rename(from: &str, to: &str) -> Result<(), ()> {
+    let (from, to) = (rom_hash(from), rom_hash(to));
+    let bytes = storage::sector(PREFERENCES_SECTOR);
+    if !decode(bytes).any(|entry| entry.rom == from) {
+        return Ok(());
+    }
+
+    let mut sector = [0; SECTOR_SIZE];
+    encode(&mut sector, renamed(decode(bytes), from, to));
+
+    storage::write(PREFERENCES_SECTOR, &sector)
+}
+
+/// `entries` with those of the ROM hashed to `from` moved to `to`, replacing
+/// any `to` had.
+fn renamed(
+    entries: impl Iterator<Item = Entry>,
+    from: u32,
+    to: u32,
+) -> impl Iterator<Item = Entry> {
+    entries
+        .filter(move |entry| entry.rom != to)
+        .map(move |entry| {
+            if entry.rom == from {
+                Entry { rom: to, ..entry }
+            } else {
+                entry
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_round_trip() {
+        let entries = [Entry { rom: 1, speed: 10 }, Entry { rom: 2, speed: 20 }];
+        let mut sector = [0; SECTOR_SIZE];
+        encode(&mut sector, entries.into_iter());
+        assert!(decode(&sector).eq(entries));
+    }
+
+    #[test]
+    fn renamed_rom_keeps_its_speed() {
+        let entries = [
+            Entry { rom: 1, speed: 10 },
+            Entry { rom: 2, speed: 20 },
+            Entry { rom: 3, speed: 30 },
+        ];
+        let mut sector = [0; SECTOR_SIZE];
+        encode(&mut sector, renamed(entries.into_iter(), 2, 3));
+        assert!(decode(&sector).eq([Entry { rom: 1, speed: 10 }, Entry { rom: 3, speed: 20 },]));
+    }
+}
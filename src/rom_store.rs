@@ -0,0 +1,152 @@
+//! CHIP-8 ROMs uploaded at runtime, kept in [`storage`] sectors. Each ROM
+//! takes one sector: a small header with the name and length, followed by the
+//! ROM. The ROMs are read in place through XIP, so they don't take any RAM.
+
+use chip8::MEMORY_SIZE;
+use heapless::{String, Vec};
+
+use crate::{
+    preferences, save_state,
+    storage::{self, SECTOR_SIZE},
+};
+
+pub const SLOTS: usize = 16;
+pub const SLOT_SIZE: usize = SECTOR_SIZE;
+pub const NAME_LEN: usize = 16;
+pub const HEADER_LEN: usize = 24;
+/// ROMs are loaded at 0x200, anything longer than the rest of the emulator's
+/// memory would be rejected when it's started.
+pub const MAX_ROM_LEN: usize = if SLOT_SIZE - HEADER_LEN < MEMORY_SIZE - 0x200 {
+    SLOT_SIZE - HEADER_LEN
+} else {
+    MEMORY_SIZE - 0x200
+};
+
+const MAGIC: [u8; 4] = *b"C8RM";
+
+#[derive(Clone, Copy)]
+pub struct StoredRom<'a> {
+    pub slot: usize,
+    pub name: &'a str,
+    pub data: &'a [u8],
+}
+
+/// Fills in the header of a slot holding a `len` byte ROM.
+pub fn encode_header(header: &mut [u8; HEADER_LEN], name: &str, len: usize) -> Result<(), ()> {
+    if name.is_empty() || name.len() > NAME_LEN || len > MAX_ROM_LEN {
+        return Err(());
+    }
+
+    *header = [0; HEADER_LEN];
+    header[..4].copy_from_slice(&MAGIC);
+    header[4..6].copy_from_slice(&(len as u16).to_le_bytes());
+    header[6] = name.len() as u8;
+    header[7..7 + name.len()].copy_from_slice(name.as_bytes());
+
+    Ok(())
+}
+
+/// Reads the ROM from the contents of slot `slot`, `None` if the slot is empty
+/// or damaged.
+pub fn decode(slot: usize, bytes: &[u8]) -> Option<StoredRom<'_>> {
+    if bytes.len() < HEADER_LEN || bytes[..4] != MAGIC {
+        return None;
+    }
+
+    let len = u16::from_le_bytes([bytes[4], bytes[5]]) as usize;
+    let name_len = bytes[6] as usize;
+    if name_len > NAME_LEN {
+        return None;
+    }
+    let name = core::str::from_utf8(&bytes[7..7 + name_len]).ok()?;
+    let data = bytes.get(HEADER_LEN..HEADER_LEN + len)?;
+
+    Some(StoredRom { slot, name, data })
+}
+
+pub fn list() -> Vec<StoredRom<'static>, SLOTS> {
+    (0..SLOTS)
+        .filter_map(|slot| decode(slot, slot_bytes(slot)))
+        .collect()
+}
+
+/// The slot to store `name` in: the one already holding a ROM of that name,
+/// or else the first empty one.
+pub fn slot_for(name: &str) -> Option<usize> {
+    let roms = list();
+    if let Some(rom) = roms.iter().find(|rom| rom.name == name) {
+        return Some(rom.slot);
+    }
+
+    (0..SLOTS).find(|slot| roms.iter().all(|rom| rom.slot != *slot))
+}
+
+fn slot_bytes(slot: usize) -> &'static [u8] {
+    // The emulator copies a ROM into its own memory on load, so a slot
+    // rewritten meanwhile only briefly leaves a stale name in the menu.
+    storage::sector(storage::ROM_SECTORS_START + slot)
+}
+
+/// Replaces the contents of `slot` with `sector`, a header followed by the ROM.
+pub fn write(slot: usize, sector: &[u8; SLOT_SIZE]) -> Result<(), ()> {
+    storage::write(storage::ROM_SECTORS_START + slot, sector)
+}
+
+/// Gives the ROM in `slot` a new name, which no other ROM may have. Its speed
+/// and save states go with it.
+pub fn rename(slot: usize, name: &str) -> Result<(), ()> {
+    let rom = decode(slot, slot_bytes(slot)).ok_or(())?;
+    if list()
+        .iter()
+        .any(|other| other.name == name && other.slot != slot)
+    {
+        return Err(());
+    }
+
+    let mut sector = [0xff; SLOT_SIZE];
+    let (header, data) = sector.split_first_chunk_mut::<HEADER_LEN>().unwrap();
+    encode_header(header, name, rom.data.len())?;
+    data[..rom.data.len()].copy_from_slice(rom.data);
+
+    // `rom` points into the slot, keep its name before rewriting it.
+    let mut old_name: String<NAME_LEN> = String::new();
+    old_name.push_str(rom.name)?;
+    write(slot, &sector)?;
+    preferences::rename(&old_name, name)?;
+    save_state::rename(&old_name, name)
+}
+
+pub fn erase(slot: usize) -> Result<(), ()> {
+    storage::erase(storage::ROM_SECTORS_START + slot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_round_trips() {
+        let mut sector = [0xff; SLOT_SIZE];
+        let (header, data) = sector.split_first_chunk_mut::<HEADER_LEN>().unwrap();
+        encode_header(header, "PONG", 3).unwrap();
+        data[..3].copy_from_slice(&[1, 2, 3]);
+
+        let rom = decode(5, &sector).unwrap();
+        assert_eq!(rom.slot, 5);
+        assert_eq!(rom.name, "PONG");
+        assert_eq!(rom.data, &[1, 2, 3]);
+    }
+
+    #[test]
+    fn rom_too_large_for_the_emulator_is_rejected() {
+        let mut header = [0; HEADER_LEN];
+        assert!(encode_header(&mut header, "BIG", MAX_ROM_LEN).is_ok());
+        assert!(encode_header(&mut header, "BIG", MAX_ROM_LEN + 1).is_err());
+        assert!(encode_header(&mut header, "BIG", MEMORY_SIZE).is_err());
+    }
+
+    #[test]
+    fn empty_slot_is_not_a_rom() {
+        assert!(decode(0, &[0xff; SLOT_SIZE]).is_none());
+    }
+}
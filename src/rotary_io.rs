@@ -4,6 +4,8 @@ use embassy_rp::{
 };
 use fixed::types::U24F8;
 
+use crate::trace;
+
 const DIVISOR: i32 = 4;
 
 const TRANSITIONS: [i32; 16] = [
@@ -87,6 +89,9 @@ impl<'d, P: Instance, const S: usize> RotaryIO<'d, P, S> {
             let new_state = self.sm.rx().wait_pull().await & 0x3;
 
             let idx = ((self.state << 2) | new_state) as usize;
+            if self.state ^ new_state == 0x3 {
+                trace!("skipped a state: {:02b} -> {:02b}", self.state, new_state);
+            }
             self.state = new_state;
 
             let sub_incr = TRANSITIONS[idx];
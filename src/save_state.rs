@@ -0,0 +1,459 @@
+//! CHIP-8 emulator snapshots kept in [`storage`] sectors. Every ROM has
+//! `STATES_PER_ROM` numbered states, shared out over `SLOTS` slots of
+//! `SLOT_SECTORS` sectors each; when all are taken, a new state replaces the
+//! one saved longest ago.
+//!
+//! ```text
+//! "C8ST" version:u8 number:u8 rom:u32 sequence:u32
+//! memory_len:u16 memory  v[16] i:u16 pc:u16 stack[16]:u16 sp:u8
+//! delay_timer:u8 sound_timer:u8  hires:u8 planes:u8 flags[16] pitch:u8
+//! pattern[16]  fb_len:u16 fb  rng_seed:u64  crc:u32
+//! ```
+//!
+//! All numbers are little endian. `rom` is the [`rom_hash`] of the ROM name
+//! and `sequence` counts up with every save. Version 2 added the SUPER-CHIP
+//! and XO-CHIP state, from `hires` to `pattern`. Version 3 added `crc`, the
+//! CRC-32 of everything before it, so a save cut short by a reset is not
+//! loaded.
+
+use chip8::{State, FB_SIZE, MEMORY_SIZE, STACK_SIZE};
+
+use crate::{
+    checksum::crc32,
+    preferences::rom_hash,
+    storage::{self, SAVE_SECTORS_START, SECTOR_SIZE},
+    warn,
+};
+
+pub const STATES_PER_ROM: usize = 3;
+pub const SLOTS: usize = 12;
+pub const SLOT_SECTORS: usize = 2;
+pub const SLOT_SIZE: usize = SLOT_SECTORS * SECTOR_SIZE;
+
+const MAGIC: [u8; 4] = *b"C8ST";
+const VERSION: u8 = 3;
+pub const HEADER_LEN: usize = 14;
+pub const ENCODED_LEN: usize = HEADER_LEN
+    + 2
+    + MEMORY_SIZE
+    + 16
+    + 2
+    + 2
+    + 2 * STACK_SIZE
+    + 3
+    + 2
+    + 16
+    + 1
+    + 16
+    + 2
+    + FB_SIZE
+    + 8
+    + 4;
+
+const _: () = assert!(ENCODED_LEN <= SLOT_SIZE);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Header {
+    /// Which of the ROM's states this is, below `STATES_PER_ROM`.
+    pub number: u8,
+    pub rom: u32,
+    pub sequence: u32,
+}
+
+impl Header {
+    pub fn decode(bytes: &[u8]) -> Option<Header> {
+        let mut reader = Reader(bytes);
+        if reader.take::<4>()? != MAGIC || reader.u8()? != VERSION {
+            return None;
+        }
+
+        Some(Header {
+            number: reader.u8()?,
+            rom: reader.u32()?,
+            sequence: reader.u32()?,
+        })
+    }
+}
+
+/// Writes `header` and `state` to the start of `buffer`, returns the length.
+pub fn encode(header: &Header, state: &State, buffer: &mut [u8; SLOT_SIZE]) -> usize {
+    let mut writer = Writer { buffer, len: 0 };
+    writer.put(&MAGIC);
+    writer.put(&[VERSION, header.number]);
+    writer.put(&header.rom.to_le_bytes());
+    writer.put(&header.sequence.to_le_bytes());
+
+    writer.put(&(MEMORY_SIZE as u16).to_le_bytes());
+    writer.put(&state.memory);
+    writer.put(&state.v);
+    writer.put(&state.i.to_le_bytes());
+    writer.put(&state.pc.to_le_bytes());
+    for address in state.stack {
+        writer.put(&address.to_le_bytes());
+    }
+    writer.put(&[state.sp, state.delay_timer, state.sound_timer]);
+    writer.put(&[state.hires as u8, state.planes]);
+    writer.put(&state.flags);
+    writer.put(&[state.pitch]);
+    writer.put(&state.pattern);
+    writer.put(&(FB_SIZE as u16).to_le_bytes());
+    writer.put(&state.fb);
+    writer.put(&state.rng_seed.to_le_bytes());
+    let crc = crc32(writer.buffer[..writer.len].iter().copied());
+    writer.put(&crc.to_le_bytes());
+
+    writer.len
+}
+
+/// Reads a state written by [`encode`], `None` if `bytes` hold none, a
+/// damaged one or one from an emulator with other memory or screen sizes.
+pub fn decode(bytes: &[u8]) -> Option<(Header, State)> {
+    let header = Header::decode(bytes)?;
+    let (data, crc) = bytes.get(..ENCODED_LEN)?.split_at(ENCODED_LEN - 4);
+    if crc32(data.iter().copied()).to_le_bytes() != crc {
+        return None;
+    }
+    let mut reader = Reader(bytes.get(HEADER_LEN..)?);
+
+    if reader.u16()? as usize != MEMORY_SIZE {
+        return None;
+    }
+    let memory = reader.take()?;
+    let v = reader.take()?;
+    let i = reader.u16()?;
+    let pc = reader.u16()?;
+    let mut stack = [0; STACK_SIZE];
+    for address in &mut stack {
+        *address = reader.u16()?;
+    }
+    let [sp, delay_timer, sound_timer] = reader.take()?;
+    let [hires, planes] = reader.take()?;
+    let flags = reader.take()?;
+    let pitch = reader.u8()?;
+    let pattern = reader.take()?;
+    if reader.u16()? as usize != FB_SIZE {
+        return None;
+    }
+    let fb = reader.take()?;
+    let rng_seed = u64::from_le_bytes(reader.take()?);
+    if sp as usize > STACK_SIZE || pc as usize >= MEMORY_SIZE || i as usize >= MEMORY_SIZE {
+        return None;
+    }
+
+    let state = State {
+        memory,
+        v,
+        i,
+        pc,
+        stack,
+        sp,
+        delay_timer,
+        sound_timer,
+        hires: hires != 0,
+        planes,
+        flags,
+        pitch,
+        pattern,
+        fb,
+        rng_seed,
+    };
+
+    Some((header, state))
+}
+
+/// Which of the states of the ROM `name` are saved.
+pub fn saved(name: &str) -> [bool; STATES_PER_ROM] {
+    let rom = rom_hash(name);
+    let mut saved = [false; STATES_PER_ROM];
+    for header in (0..SLOTS).filter_map(header) {
+        if header.rom == rom {
+            if let Some(number) = saved.get_mut(header.number as usize) {
+                *number = true;
+            }
+        }
+    }
+
+    saved
+}
+
+pub fn load(name: &str, number: usize) -> Option<State> {
+    let rom = rom_hash(name);
+    let slot = (0..SLOTS).find(|&slot| {
+        header(slot).is_some_and(|header| header.rom == rom && header.number as usize == number)
+    })?;
+
+    let (_, state) = decode(slot_bytes(slot))?;
+    Some(state)
+}
+
+pub fn save(name: &str, number: usize, state: &State) -> Result<(), ()> {
+    let rom = rom_hash(name);
+    let headers: [Option<Header>; SLOTS] = core::array::from_fn(header);
+    let sequence = headers
+        .iter()
+        .flatten()
+        .map(|header| header.sequence + 1)
+        .max()
+        .unwrap_or(0);
+
+    // The slot already holding this state, else an empty one, else the oldest.
+    let slot = headers
+        .iter()
+        .position(|header| {
+            header.is_some_and(|header| header.rom == rom && header.number as usize == number)
+        })
+        .or_else(|| headers.iter().position(Option::is_none))
+        .or_else(|| {
+            (0..SLOTS).min_by_key(|&slot| headers[slot].map_or(0, |header| header.sequence))
+        })
+        .ok_or(())?;
+    if headers[slot].is_some_and(|old| old.rom != rom) {
+        warn!("no free save slot, replacing the oldest state");
+    }
+
+    let header = Header {
+        number: number as u8,
+        rom,
+        sequence,
+    };
+    let mut buffer = [0xff; SLOT_SIZE];
+    encode(&header, state, &mut buffer);
+
+    write_slot(slot, &buffer)
+}
+
+/// Moves the states of the ROM `from` over to the ROM `to`, replacing any `to`
+/// had.
+pub fn rename(from: &str, to: &str) -> Result<(), ()> {
+    let (from, to) = (rom_hash(from), rom_hash(to));
+    if from == to {
+        return Ok(());
+    }
+
+    for slot in 0..SLOTS {
+        if header(slot).is_some_and(|header| header.rom == to) {
+            storage::erase(SAVE_SECTORS_START + slot * SLOT_SECTORS)?;
+        }
+    }
+    for slot in 0..SLOTS {
+        if header(slot).is_none_or(|header| header.rom != from) {
+            continue;
+        }
+        let mut buffer = [0xff; SLOT_SIZE];
+        if rehash(slot_bytes(slot), to, &mut buffer).is_some() {
+            write_slot(slot, &buffer)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes the state in `bytes` to `buffer` as one of the ROM hashed to `rom`.
+fn rehash(bytes: &[u8], rom: u32, buffer: &mut [u8; SLOT_SIZE]) -> Option<()> {
+    let (header, state) = decode(bytes)?;
+    encode(&Header { rom, ..header }, &state, buffer);
+
+    Some(())
+}
+
+fn write_slot(slot: usize, buffer: &[u8; SLOT_SIZE]) -> Result<(), ()> {
+    // The header goes last, so a reset in between leaves no header or the
+    // old one, which the CRC then rejects.
+    for (index, sector) in buffer.chunks_exact(SECTOR_SIZE).enumerate().rev() {
+        storage::write(
+            SAVE_SECTORS_START + slot * SLOT_SECTORS + index,
+            sector.try_into().unwrap(),
+        )?;
+    }
+
+    Ok(())
+}
+
+fn header(slot: usize) -> Option<Header> {
+    Header::decode(slot_bytes(slot))
+}
+
+fn slot_bytes(slot: usize) -> &'static [u8] {
+    storage::sectors(SAVE_SECTORS_START + slot * SLOT_SECTORS, SLOT_SECTORS)
+}
+
+struct Writer<'a> {
+    buffer: &'a mut [u8; SLOT_SIZE],
+    len: usize,
+}
+
+impl Writer<'_> {
+    fn put(&mut self, bytes: &[u8]) {
+        self.buffer[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let (bytes, rest) = self.0.split_first_chunk::<N>()?;
+        self.0 = rest;
+        Some(*bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take::<1>().map(|[byte]| byte)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take().map(u16::from_le_bytes)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take().map(u32::from_le_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chip8::{Chip8, Quirks, Variant};
+    use rand::rngs::SmallRng;
+
+    use super::*;
+
+    /// Draws a box at a random place from a subroutine, with the timers set
+    /// from the registers, forever.
+    const ROM: [u8; 27] = [
+        0x00, 0xe0, // CLS
+        0xa2, 0x16, // LD I, 216
+        0xc0, 0x3f, // RND V0, 3F
+        0xc1, 0x1f, // RND V1, 1F
+        0x22, 0x10, // CALL 210
+        0xf0, 0x15, // LD DT, V0
+        0xf2, 0x18, // LD ST, V2
+        0x12, 0x04, // JP 204
+        0xd0, 0x15, // DRW V0, V1, 5
+        0x72, 0x01, // ADD V2, 1
+        0x00, 0xee, // RET
+        0xf0, 0x90, 0x90, 0x90, 0xf0,
+    ];
+
+    const HEADER: Header = Header {
+        number: 2,
+        rom: 0x1234_5678,
+        sequence: 7,
+    };
+
+    fn play(emulator: &mut Chip8<SmallRng>, frames: usize) {
+        for _ in 0..frames {
+            for _ in 0..10 {
+                emulator.step(0).unwrap();
+            }
+            emulator.tick_timers();
+        }
+    }
+
+    fn emulator(variant: Variant, frames: usize) -> Chip8<SmallRng> {
+        let mut emulator = Chip8::<SmallRng>::new(1, variant, Quirks::default());
+        emulator.load_rom(&ROM).unwrap();
+        play(&mut emulator, frames);
+
+        emulator
+    }
+
+    fn encoded(state: &State) -> [u8; SLOT_SIZE] {
+        let mut buffer = [0xff; SLOT_SIZE];
+        assert_eq!(encode(&HEADER, state, &mut buffer), ENCODED_LEN);
+
+        buffer
+    }
+
+    #[test]
+    fn restored_emulator_carries_on_the_same() {
+        for variant in [Variant::Chip8, Variant::SuperChip] {
+            let mut emulator = emulator(variant, 120);
+            let state = emulator.state();
+            let (header, decoded) = decode(&encoded(&state)).unwrap();
+            assert_eq!(header, HEADER);
+            assert_eq!(decoded, state);
+
+            let mut restored = Chip8::<SmallRng>::new(2, variant, Quirks::default());
+            restored.restore(&decoded);
+            play(&mut emulator, 60);
+            play(&mut restored, 60);
+            assert_eq!(restored.state(), emulator.state());
+        }
+    }
+
+    #[test]
+    fn rehashed_state_belongs_to_the_new_name() {
+        let state = emulator(Variant::Chip8, 10).state();
+        let mut buffer = [0xff; SLOT_SIZE];
+        rehash(&encoded(&state), 0x9abc_def0, &mut buffer).unwrap();
+
+        let (header, decoded) = decode(&buffer).unwrap();
+        assert_eq!(
+            header,
+            Header {
+                rom: 0x9abc_def0,
+                ..HEADER
+            }
+        );
+        assert_eq!(decoded, state);
+    }
+
+    #[test]
+    fn other_version_is_rejected() {
+        let mut buffer = encoded(&emulator(Variant::Chip8, 10).state());
+        buffer[4] += 1;
+        assert!(decode(&buffer).is_none());
+    }
+
+    #[test]
+    fn truncated_state_is_rejected() {
+        let buffer = encoded(&emulator(Variant::Chip8, 10).state());
+        assert!(decode(&buffer[..ENCODED_LEN - 1]).is_none());
+    }
+
+    #[test]
+    fn torn_write_is_rejected() {
+        let mut buffer = encoded(&emulator(Variant::Chip8, 10).state());
+        buffer[SECTOR_SIZE..].fill(0xff);
+        assert!(decode(&buffer).is_none());
+
+        // Cut off while replacing an older state of the same slot.
+        let mut buffer = encoded(&emulator(Variant::Chip8, 10).state());
+        let mut newer = [0xff; SLOT_SIZE];
+        let header = Header {
+            sequence: HEADER.sequence + 1,
+            ..HEADER
+        };
+        encode(&header, &emulator(Variant::Chip8, 20).state(), &mut newer);
+        buffer[SECTOR_SIZE..].copy_from_slice(&newer[SECTOR_SIZE..]);
+        assert!(decode(&buffer).is_none());
+    }
+
+    #[test]
+    fn out_of_range_registers_are_rejected() {
+        let state = emulator(Variant::Chip8, 10).state();
+        assert!(decode(&encoded(&State {
+            sp: STACK_SIZE as u8,
+            ..state.clone()
+        }))
+        .is_some());
+
+        for damaged in [
+            State {
+                sp: STACK_SIZE as u8 + 1,
+                ..state.clone()
+            },
+            State {
+                pc: MEMORY_SIZE as u16,
+                ..state.clone()
+            },
+            State {
+                i: MEMORY_SIZE as u16,
+                ..state.clone()
+            },
+        ] {
+            assert!(decode(&encoded(&damaged)).is_none());
+        }
+    }
+}
@@ -0,0 +1,341 @@
+//! Burn-in protection for the OLED. Without input the panel is dimmed, then
+//! shows a drifting clock and is finally switched off. The input that wakes it
+//! up is swallowed, so apps never see it.
+//!
+//! Apps draw into a [`FrameBuffer`] of their own through [`ScreenDisplay`],
+//! which is copied to the panel on flush while the screen is awake. That way
+//! the app's last frame comes back on wake-up without the app redrawing.
+
+use core::{cell::RefCell, fmt::Write};
+
+use embassy_futures::select::{select, Either};
+use embassy_time::{Duration, Instant, Timer};
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    mono_font::{ascii::FONT_10X20, MonoTextStyle},
+    pixelcolor::BinaryColor,
+    prelude::{OriginDimensions, Pixel, Point, Size},
+    text::{Baseline, Text},
+    Drawable,
+};
+use heapless::String;
+
+use crate::{
+    debug,
+    framebuffer::FrameBuffer,
+    hal::{Display, Input},
+    input_handler::{InputEvent, InputSource},
+    status_bar,
+};
+
+pub const DIM_AFTER: Duration = Duration::from_secs(30);
+pub const SCREEN_SAVER_AFTER: Duration = Duration::from_secs(2 * 60);
+pub const OFF_AFTER: Duration = Duration::from_secs(10 * 60);
+
+pub const NORMAL_CONTRAST: u8 = 0x80;
+pub const DIM_CONTRAST: u8 = 0x08;
+
+const SCREEN_SAVER_STEP: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ScreenState {
+    On,
+    Dimmed,
+    ScreenSaver,
+    Off,
+}
+
+impl ScreenState {
+    /// The state the screen should be in after `idle` without input.
+    pub fn after(idle: Duration) -> Self {
+        if idle >= OFF_AFTER {
+            ScreenState::Off
+        } else if idle >= SCREEN_SAVER_AFTER {
+            ScreenState::ScreenSaver
+        } else if idle >= DIM_AFTER {
+            ScreenState::Dimmed
+        } else {
+            ScreenState::On
+        }
+    }
+
+    pub fn is_awake(self) -> bool {
+        matches!(self, ScreenState::On | ScreenState::Dimmed)
+    }
+}
+
+/// Position of an item of `size` after `step` moves, bouncing off the edges of
+/// `area`.
+pub fn drift(step: u32, area: Size, size: Size) -> Point {
+    fn bounce(position: u32, range: u32) -> i32 {
+        if range == 0 {
+            return 0;
+        }
+        let position = position % (2 * range);
+        (if position < range {
+            position
+        } else {
+            2 * range - position
+        }) as i32
+    }
+
+    Point::new(
+        bounce(step * 3, area.width.saturating_sub(size.width)),
+        bounce(step * 2, area.height.saturating_sub(size.height)),
+    )
+}
+
+struct State<D> {
+    panel: D,
+    frame: FrameBuffer,
+    state: ScreenState,
+    last_activity: Instant,
+    screen_saver_step: u32,
+    screen_saver_drawn: Instant,
+    /// The key or button whose press woke the screen, so that its release is
+    /// swallowed as well.
+    wake_source: Option<InputSource>,
+}
+
+pub struct Screen<D> {
+    state: RefCell<State<D>>,
+}
+
+impl<D: Display> Screen<D> {
+    pub fn new(panel: D) -> Self {
+        Screen {
+            state: RefCell::new(State {
+                panel,
+                frame: FrameBuffer::new(),
+                state: ScreenState::On,
+                last_activity: Instant::now(),
+                screen_saver_step: 0,
+                screen_saver_drawn: Instant::now(),
+                wake_source: None,
+            }),
+        }
+    }
+
+    /// The display handle to give to apps.
+    pub fn display(&self) -> ScreenDisplay<'_, D> {
+        ScreenDisplay { screen: self }
+    }
+
+    /// Wraps `input` so that it keeps the screen awake.
+    pub fn input<I: Input>(&self, input: I) -> ScreenInput<'_, D, I> {
+        ScreenInput {
+            screen: self,
+            input,
+        }
+    }
+
+    pub fn state(&self) -> ScreenState {
+        self.state.borrow().state
+    }
+
+    /// Records input. Returns `false` if the event only woke the screen and
+    /// must not be passed on.
+    fn on_event(&self, event: &InputEvent) -> bool {
+        let mut state = self.state.borrow_mut();
+        state.last_activity = Instant::now();
+
+        if let InputEvent::Released(source) = event {
+            if state
+                .wake_source
+                .as_ref()
+                .is_some_and(|wake| same_source(wake, source))
+            {
+                state.wake_source = None;
+                return false;
+            }
+        }
+
+        if state.state.is_awake() {
+            if state.state == ScreenState::Dimmed {
+                state.set_state(ScreenState::On);
+            }
+            return true;
+        }
+
+        if let InputEvent::Pressed(source) = event {
+            state.wake_source = Some(source.clone());
+        }
+        state.set_state(ScreenState::On);
+
+        false
+    }
+
+    /// Applies timeouts and animates the screen saver.
+    fn update(&self) {
+        let mut state = self.state.borrow_mut();
+        let new_state = ScreenState::after(state.last_activity.elapsed());
+        if new_state != state.state {
+            state.set_state(new_state);
+        } else if state.state == ScreenState::ScreenSaver
+            && state.screen_saver_drawn.elapsed() >= SCREEN_SAVER_STEP
+        {
+            state.draw_screen_saver();
+        }
+    }
+
+    /// When `update` has something to do next.
+    fn next_deadline(&self) -> Instant {
+        let state = self.state.borrow();
+        match state.state {
+            ScreenState::On => state.last_activity + DIM_AFTER,
+            ScreenState::Dimmed => state.last_activity + SCREEN_SAVER_AFTER,
+            ScreenState::ScreenSaver => {
+                (state.last_activity + OFF_AFTER).min(state.screen_saver_drawn + SCREEN_SAVER_STEP)
+            }
+            ScreenState::Off => Instant::MAX,
+        }
+    }
+}
+
+impl<D: Display> State<D> {
+    fn set_state(&mut self, new_state: ScreenState) {
+        debug!("{:?} -> {:?}", self.state, new_state);
+        let old_state = core::mem::replace(&mut self.state, new_state);
+
+        // Panel errors are not fatal here, the next transition tries again.
+        if old_state == ScreenState::Off {
+            let _ = self.panel.set_on(true);
+        }
+        match new_state {
+            ScreenState::On => {
+                let _ = self.panel.set_contrast(NORMAL_CONTRAST);
+                let _ = self.show_frame();
+            }
+            ScreenState::Dimmed => {
+                let _ = self.panel.set_contrast(DIM_CONTRAST);
+            }
+            ScreenState::ScreenSaver => {
+                let _ = self.panel.set_contrast(DIM_CONTRAST);
+                self.draw_screen_saver();
+            }
+            ScreenState::Off => {
+                let _ = self.panel.set_on(false);
+            }
+        }
+    }
+
+    fn show_frame(&mut self) -> Result<(), D::Error> {
+        self.panel.clear_buffer();
+        self.panel.draw_iter(self.frame.lit_pixels())?;
+        self.panel.flush()
+    }
+
+    fn draw_screen_saver(&mut self) {
+        let style = MonoTextStyle::new(&FONT_10X20, BinaryColor::On);
+        let mut time: String<5> = String::new();
+        let _ = match status_bar::time_of_day() {
+            Some((hours, minutes)) => write!(time, "{:02}:{:02}", hours, minutes),
+            None => time.write_str("--:--"),
+        };
+        let text_size = Size::new(
+            time.len() as u32 * FONT_10X20.character_size.width,
+            FONT_10X20.character_size.height,
+        );
+        let position = drift(self.screen_saver_step, self.panel.size(), text_size);
+
+        self.panel.clear_buffer();
+        let _ = Text::with_baseline(&time, position, style, Baseline::Top).draw(&mut self.panel);
+        let _ = self.panel.flush();
+
+        self.screen_saver_step = self.screen_saver_step.wrapping_add(1);
+        self.screen_saver_drawn = Instant::now();
+    }
+}
+
+fn same_source(a: &InputSource, b: &InputSource) -> bool {
+    match (a, b) {
+        (InputSource::Button, InputSource::Button) => true,
+        (InputSource::Key(a), InputSource::Key(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Draws into the app frame, which only reaches the panel while it is awake.
+pub struct ScreenDisplay<'a, D> {
+    screen: &'a Screen<D>,
+}
+
+impl<D: Display> OriginDimensions for ScreenDisplay<'_, D> {
+    fn size(&self) -> Size {
+        self.screen.state.borrow().frame.size()
+    }
+}
+
+impl<D: Display> DrawTarget for ScreenDisplay<'_, D> {
+    type Color = BinaryColor;
+    type Error = D::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        // Drawing into a `FrameBuffer` cannot fail.
+        let _ = self.screen.state.borrow_mut().frame.draw_iter(pixels);
+
+        Ok(())
+    }
+}
+
+impl<D: Display> Display for ScreenDisplay<'_, D> {
+    fn clear_buffer(&mut self) {
+        self.screen.state.borrow_mut().frame.clear();
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        let mut state = self.screen.state.borrow_mut();
+        if state.state.is_awake() {
+            state.show_frame()
+        } else {
+            Ok(())
+        }
+    }
+
+    fn set_contrast(&mut self, contrast: u8) -> Result<(), Self::Error> {
+        self.screen.state.borrow_mut().panel.set_contrast(contrast)
+    }
+
+    fn set_on(&mut self, on: bool) -> Result<(), Self::Error> {
+        self.screen.state.borrow_mut().panel.set_on(on)
+    }
+}
+
+pub struct ScreenInput<'a, D, I> {
+    screen: &'a Screen<D>,
+    input: I,
+}
+
+impl<D: Display, I: Input> Input for ScreenInput<'_, D, I> {
+    async fn next_event(&mut self) -> InputEvent {
+        loop {
+            match select(
+                self.input.next_event(),
+                Timer::at(self.screen.next_deadline()),
+            )
+            .await
+            {
+                Either::First(event) => {
+                    if self.screen.on_event(&event) {
+                        return event;
+                    }
+                }
+                Either::Second(_) => self.screen.update(),
+            }
+        }
+    }
+
+    fn try_next_event(&mut self) -> Option<InputEvent> {
+        self.screen.update();
+
+        loop {
+            let event = self.input.try_next_event()?;
+            if self.screen.on_event(&event) {
+                return Some(event);
+            }
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use core::{
+    cell::RefCell,
+    fmt::Write,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use embassy_sync::blocking_mutex::{raw::CriticalSectionRawMutex, Mutex};
+use embassy_time::{Duration, Instant};
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    image::{Image, ImageRaw},
+    mono_font::{ascii::FONT_5X7, MonoTextStyle},
+    pixelcolor::BinaryColor,
+    prelude::{Point, Primitive, Size},
+    primitives::{Line, PrimitiveStyle, Rectangle},
+    text::{Alignment, Baseline, Text, TextStyleBuilder},
+    Drawable,
+};
+use heapless::String;
+
+use crate::datetime::DateTime;
+
+pub const STATUS_BAR_HEIGHT: u32 = 9;
+pub const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+#[rustfmt::skip]
+const USB_ICON: [u8; 7] = [
+    0b00100100,
+    0b00100100,
+    0b01111110,
+    0b01111110,
+    0b00111100,
+    0b00011000,
+    0b00011000,
+];
+
+struct Status {
+    clock: Option<(DateTime, Instant)>,
+    usb_connected: bool,
+    mode: &'static str,
+    frame_time: Option<Duration>,
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+static STATUS: Mutex<CriticalSectionRawMutex, RefCell<Status>> = Mutex::new(RefCell::new(Status {
+    clock: None,
+    usb_connected: false,
+    mode: "",
+    frame_time: None,
+}));
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Anchors the displayed clock to a time read from the RTC. The bar keeps
+/// counting from there using the embassy time driver, so the I2C bus does not
+/// have to be polled on every redraw.
+pub fn sync_clock(datetime: &DateTime) {
+    STATUS.lock(|status| status.borrow_mut().clock = Some((*datetime, Instant::now())));
+}
+
+/// Current hours and minutes, if the clock has been synced.
+pub fn time_of_day() -> Option<(u8, u8)> {
+    let (datetime, synced_at) = STATUS.lock(|status| status.borrow().clock)?;
+    let seconds = (datetime.hours as u64 * 60 + datetime.minutes as u64) * 60
+        + datetime.seconds as u64
+        + synced_at.elapsed().as_secs();
+    let seconds = seconds % SECONDS_PER_DAY;
+
+    Some(((seconds / 3600) as u8, (seconds / 60 % 60) as u8))
+}
+
+pub fn set_usb_connected(connected: bool) {
+    STATUS.lock(|status| status.borrow_mut().usb_connected = connected);
+}
+
+pub fn set_mode(mode: &'static str) {
+    STATUS.lock(|status| status.borrow_mut().mode = mode);
+}
+
+/// Shows the display frame time next to the USB icon, or hides it with `None`.
+pub fn set_frame_time(frame_time: Option<Duration>) {
+    STATUS.lock(|status| status.borrow_mut().frame_time = frame_time);
+}
+
+/// The part of the display left for app content.
+pub fn content_area(display_size: Size) -> Rectangle {
+    if is_enabled() {
+        Rectangle::new(
+            Point::new(0, STATUS_BAR_HEIGHT as i32),
+            Size::new(
+                display_size.width,
+                display_size.height.saturating_sub(STATUS_BAR_HEIGHT),
+            ),
+        )
+    } else {
+        Rectangle::new(Point::zero(), display_size)
+    }
+}
+
+/// Draws the status bar into the top rows of `target` if it is enabled.
+pub fn draw<D>(target: &mut D) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    if !is_enabled() {
+        return Ok(());
+    }
+
+    let (usb_connected, mode, frame_time) = STATUS.lock(|status| {
+        let status = status.borrow();
+        (status.usb_connected, status.mode, status.frame_time)
+    });
+
+    let width = target.bounding_box().size.width;
+    let text_style = MonoTextStyle::new(&FONT_5X7, BinaryColor::On);
+
+    Rectangle::new(Point::zero(), Size::new(width, STATUS_BAR_HEIGHT))
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::Off))
+        .draw(target)?;
+
+    let mut time: String<5> = String::new();
+    match time_of_day() {
+        Some((hours, minutes)) => {
+            let _ = write!(time, "{:02}:{:02}", hours, minutes);
+        }
+        None => {
+            let _ = time.push_str("--:--");
+        }
+    }
+    Text::with_baseline(&time, Point::zero(), text_style, Baseline::Top).draw(target)?;
+
+    Text::with_text_style(
+        mode,
+        Point::new(width as i32 / 2, 0),
+        text_style,
+        TextStyleBuilder::new()
+            .alignment(Alignment::Center)
+            .baseline(Baseline::Top)
+            .build(),
+    )
+    .draw(target)?;
+
+    if let Some(frame_time) = frame_time {
+        let mut text: String<8> = String::new();
+        let _ = write!(text, "{}ms", frame_time.as_millis());
+        Text::with_text_style(
+            &text,
+            Point::new(width as i32 - 9, 0),
+            text_style,
+            TextStyleBuilder::new()
+                .alignment(Alignment::Right)
+                .baseline(Baseline::Top)
+                .build(),
+        )
+        .draw(target)?;
+    }
+
+    if usb_connected {
+        let icon = ImageRaw::<BinaryColor>::new(&USB_ICON, 8);
+        Image::new(&icon, Point::new(width as i32 - 8, 0)).draw(target)?;
+    }
+
+    Line::new(
+        Point::new(0, STATUS_BAR_HEIGHT as i32 - 1),
+        Point::new(width as i32 - 1, STATUS_BAR_HEIGHT as i32 - 1),
+    )
+    .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
+    .draw(target)?;
+
+    Ok(())
+}
@@ -0,0 +1,108 @@
+//! The flash area reserved in `memory.x` for data written at runtime, split
+//! into erase sectors. Sectors are read in place through XIP and rewritten as a
+//! whole.
+//!
+//! Without the `rp2040` feature there is no flash: sectors read as empty and
+//! writes are dropped.
+
+#[cfg(feature = "rp2040")]
+use core::cell::RefCell;
+
+#[cfg(feature = "rp2040")]
+use embassy_rp::{
+    flash::{Blocking, Flash},
+    peripherals::FLASH,
+};
+#[cfg(feature = "rp2040")]
+use embassy_sync::blocking_mutex::{raw::CriticalSectionRawMutex, Mutex};
+
+#[cfg(feature = "rp2040")]
+use crate::error;
+
+/// Size of the flash as declared in `memory.x`.
+pub const FLASH_SIZE: usize = 2048 * 1024;
+pub const SECTOR_SIZE: usize = 4096;
+pub const SECTORS: usize = 41;
+/// Offset of the reserved area, the last `SECTORS` sectors before `FLASH_SIZE`.
+pub const OFFSET: usize = FLASH_SIZE - SECTORS * SECTOR_SIZE;
+
+/// Sector of the [`preferences`](crate::preferences).
+pub const PREFERENCES_SECTOR: usize = 0;
+/// First of the [`rom_store`](crate::rom_store) sectors.
+pub const ROM_SECTORS_START: usize = 1;
+/// First of the [`save_state`](crate::save_state) sectors.
+pub const SAVE_SECTORS_START: usize = 17;
+
+#[cfg(feature = "rp2040")]
+pub type StorageFlash = Flash<'static, FLASH, Blocking, FLASH_SIZE>;
+
+#[cfg(feature = "rp2040")]
+static FLASH: Mutex<CriticalSectionRawMutex, RefCell<Option<StorageFlash>>> =
+    Mutex::new(RefCell::new(None));
+
+/// Hands over the flash. Writes fail until this was called.
+#[cfg(feature = "rp2040")]
+pub fn init(flash: StorageFlash) {
+    FLASH.lock(|cell| *cell.borrow_mut() = Some(flash));
+}
+
+pub fn sector(index: usize) -> &'static [u8] {
+    sectors(index, 1)
+}
+
+/// The contents of `count` consecutive sectors starting at `first`.
+#[cfg(feature = "rp2040")]
+pub fn sectors(first: usize, count: usize) -> &'static [u8] {
+    const XIP_BASE: usize = 0x1000_0000;
+
+    assert!(first + count <= SECTORS);
+    let address = XIP_BASE + OFFSET + first * SECTOR_SIZE;
+    // The reserved area is always mapped. Its contents change under `write`
+    // and `erase`, readers copy what they need to keep.
+    unsafe { core::slice::from_raw_parts(address as *const u8, count * SECTOR_SIZE) }
+}
+
+#[cfg(not(feature = "rp2040"))]
+pub fn sectors(_first: usize, _count: usize) -> &'static [u8] {
+    &[]
+}
+
+/// Replaces the contents of sector `index`.
+#[cfg(feature = "rp2040")]
+pub fn write(index: usize, data: &[u8; SECTOR_SIZE]) -> Result<(), ()> {
+    let offset = (OFFSET + index * SECTOR_SIZE) as u32;
+    with_flash(|flash| {
+        flash.blocking_erase(offset, offset + SECTOR_SIZE as u32)?;
+        flash.blocking_write(offset, data)
+    })
+}
+
+#[cfg(not(feature = "rp2040"))]
+pub fn write(_index: usize, _data: &[u8; SECTOR_SIZE]) -> Result<(), ()> {
+    Ok(())
+}
+
+#[cfg(feature = "rp2040")]
+pub fn erase(index: usize) -> Result<(), ()> {
+    let offset = (OFFSET + index * SECTOR_SIZE) as u32;
+    with_flash(|flash| flash.blocking_erase(offset, offset + SECTOR_SIZE as u32))
+}
+
+#[cfg(not(feature = "rp2040"))]
+pub fn erase(_index: usize) -> Result<(), ()> {
+    Ok(())
+}
+
+#[cfg(feature = "rp2040")]
+fn with_flash(
+    f: impl FnOnce(&mut StorageFlash) -> Result<(), embassy_rp::flash::Error>,
+) -> Result<(), ()> {
+    FLASH.lock(|cell| {
+        let mut flash = cell.borrow_mut();
+        let Some(flash) = flash.as_mut() else {
+            error!("flash storage used before init");
+            return Err(());
+        };
+        f(flash).map_err(|e| error!("flash write failed: {:?}", e))
+    })
+}
@@ -0,0 +1,386 @@
+use embassy_futures::select::{select, Either};
+use embassy_time::{Duration, Timer};
+use embedded_graphics::{
+    draw_target::{DrawTarget, DrawTargetExt},
+    mono_font::{ascii::FONT_6X10, MonoTextStyle, MonoTextStyleBuilder},
+    pixelcolor::BinaryColor,
+    prelude::{Point, Primitive},
+    primitives::{Line, PrimitiveStyle},
+    text::{Baseline, Text},
+    Drawable,
+};
+use heapless::String;
+
+use crate::{
+    hal::{Display, Input},
+    input_handler::{InputEvent, InputSource},
+    status_bar,
+};
+
+const MULTI_TAP_TIMEOUT: Duration = Duration::from_millis(1000);
+
+const KEY_BACKSPACE: usize = 9;
+const KEY_SHIFT: usize = 11;
+
+// Phone-style layout on the 3x4 key grid; `*` and `#` are the special keys.
+const KEY_CHARS: [&str; 12] = [
+    ".,?!1", "abc2", "def3", //
+    "ghi4", "jkl5", "mno6", //
+    "pqrs7", "tuv8", "wxyz9", //
+    "", " 0", "",
+];
+
+const WHEEL_CHARS: &str = "abcdefghijklmnopqrstuvwxyz0123456789 .,:;-_!?'\"()/+*=#@&%";
+
+#[derive(Clone, Copy)]
+enum Pending {
+    Key { key: usize, index: usize },
+    Wheel { index: usize },
+}
+
+impl Pending {
+    fn char(self, uppercase: bool) -> char {
+        let c = match self {
+            Pending::Key { key, index } => KEY_CHARS[key].as_bytes()[index] as char,
+            Pending::Wheel { index } => WHEEL_CHARS.as_bytes()[index] as char,
+        };
+
+        if uppercase {
+            c.to_ascii_uppercase()
+        } else {
+            c
+        }
+    }
+}
+
+pub struct TextEntry<'a, const N: usize> {
+    prompt: &'a str,
+    text: String<N>,
+    pending: Option<Pending>,
+    uppercase: bool,
+}
+
+impl<'a, const N: usize> TextEntry<'a, N> {
+    pub fn new(prompt: &'a str) -> Self {
+        TextEntry {
+            prompt,
+            text: String::new(),
+            pending: None,
+            uppercase: false,
+        }
+    }
+
+    /// Starts out with `text`, leaving out anything but ASCII as the wheel and
+    /// the keys only enter ASCII.
+    pub fn with_text(mut self, text: &str) -> Self {
+        self.text.clear();
+        for c in text.chars().filter(char::is_ascii) {
+            if self.text.push(c).is_err() {
+                break;
+            }
+        }
+
+        self
+    }
+
+    /// Lets the user edit the text until the encoder button is pressed with no
+    /// pending character. Returns `None` if backspace is pressed on an empty
+    /// text.
+    pub async fn run<D, I>(&mut self, display: &mut D, input: &mut I) -> Option<String<N>>
+    where
+        D: Display,
+        I: Input,
+    {
+        let content_area = status_bar::content_area(display.size());
+
+        display.clear_buffer();
+        status_bar::draw(display).ok()?;
+        self.draw(&mut display.cropped(&content_area)).ok()?;
+        display.flush().ok()?;
+
+        loop {
+            let msg = match self.pending {
+                Some(Pending::Key { .. }) => {
+                    match select(input.next_event(), Timer::after(MULTI_TAP_TIMEOUT)).await {
+                        Either::First(msg) => msg,
+                        Either::Second(_) => {
+                            self.commit_pending();
+                            self.draw(&mut display.cropped(&content_area)).ok()?;
+                            display.flush().ok()?;
+                            continue;
+                        }
+                    }
+                }
+                _ => input.next_event().await,
+            };
+
+            match msg {
+                InputEvent::Pressed(InputSource::Button) => {
+                    if self.pending.is_none() {
+                        return Some(self.text.clone());
+                    }
+                    self.commit_pending();
+                }
+                InputEvent::Pressed(InputSource::Key(KEY_BACKSPACE)) => {
+                    if self.pending.take().is_none() && self.text.pop().is_none() {
+                        return None;
+                    }
+                }
+                InputEvent::Pressed(InputSource::Key(KEY_SHIFT)) => {
+                    self.uppercase = !self.uppercase;
+                }
+                InputEvent::Pressed(InputSource::Key(key)) => {
+                    self.tap_key(key);
+                }
+                InputEvent::TurnedCW(_) => {
+                    self.turn_wheel(true);
+                }
+                InputEvent::TurnedCCW(_) => {
+                    self.turn_wheel(false);
+                }
+                _ => continue,
+            }
+
+            self.draw(&mut display.cropped(&content_area)).ok()?;
+            display.flush().ok()?;
+        }
+    }
+
+    fn tap_key(&mut self, key: usize) {
+        let num_chars = KEY_CHARS[key].len();
+        if num_chars == 0 {
+            return;
+        }
+
+        match self.pending {
+            Some(Pending::Key {
+                key: pending_key,
+                index,
+            }) if pending_key == key => {
+                self.pending = Some(Pending::Key {
+                    key,
+                    index: (index + 1) % num_chars,
+                });
+            }
+            _ => {
+                self.commit_pending();
+                if !self.is_full() {
+                    self.pending = Some(Pending::Key { key, index: 0 });
+                }
+            }
+        }
+    }
+
+    fn turn_wheel(&mut self, forward: bool) {
+        let num_chars = WHEEL_CHARS.len();
+
+        match self.pending {
+            Some(Pending::Wheel { index }) => {
+                let index = if forward {
+                    (index + 1) % num_chars
+                } else {
+                    (index + num_chars - 1) % num_chars
+                };
+                self.pending = Some(Pending::Wheel { index });
+            }
+            _ => {
+                self.commit_pending();
+                if !self.is_full() {
+                    self.pending = Some(Pending::Wheel { index: 0 });
+                }
+            }
+        }
+    }
+
+    fn commit_pending(&mut self) {
+        if let Some(pending) = self.pending.take() {
+            let _ = self.text.push(pending.char(self.uppercase));
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.text.len() >= N
+    }
+}
+
+impl<const N: usize> Drawable for TextEntry<'_, N> {
+    type Color = BinaryColor;
+    type Output = ();
+
+    fn draw<D>(&self, target: &mut D) -> Result<Self::Output, D::Error>
+    where
+        D: DrawTarget<Color = Self::Color>,
+    {
+        let font_width = FONT_6X10.character_size.width as i32;
+        let font_height = FONT_6X10.character_size.height as i32;
+        let bounding_box = target.bounding_box();
+        let target_width = bounding_box.size.width as i32;
+        let target_height = bounding_box.size.height as i32;
+
+        let normal_text_style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        let inverted_text_style = MonoTextStyleBuilder::new()
+            .font(&FONT_6X10)
+            .text_color(BinaryColor::Off)
+            .background_color(BinaryColor::On)
+            .build();
+
+        target.clear(BinaryColor::Off)?;
+
+        Text::with_baseline(self.prompt, Point::zero(), normal_text_style, Baseline::Top)
+            .draw(target)?;
+
+        // keep the end of the text and the cursor in view
+        let max_chars = (target_width / font_width - 1) as usize;
+        let visible_len = self.text.len() + usize::from(self.pending.is_some());
+        let skip = visible_len.saturating_sub(max_chars);
+        let visible_text = &self.text[skip.min(self.text.len())..];
+
+        let text_position = Point::new(0, (target_height - font_height) / 2);
+        let next_position = Text::with_baseline(
+            visible_text,
+            text_position,
+            normal_text_style,
+            Baseline::Top,
+        )
+        .draw(target)?;
+
+        let cursor_position = if let Some(pending) = self.pending {
+            let mut buffer = [0; 4];
+            let pending_char = pending.char(self.uppercase).encode_utf8(&mut buffer);
+            Text::with_baseline(
+                pending_char,
+                next_position,
+                inverted_text_style,
+                Baseline::Top,
+            )
+            .draw(target)?
+        } else {
+            next_position
+        };
+
+        Line::new(
+            cursor_position + Point::new(0, font_height),
+            cursor_position + Point::new(font_width - 1, font_height),
+        )
+        .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
+        .draw(target)?;
+
+        let hint = if self.uppercase {
+            "*:Del #:abc Btn:OK"
+        } else {
+            "*:Del #:ABC Btn:OK"
+        };
+        Text::with_baseline(
+            hint,
+            Point::new(0, target_height - font_height),
+            normal_text_style,
+            Baseline::Top,
+        )
+        .draw(target)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "host"))]
+mod tests {
+    use embassy_futures::block_on;
+
+    use super::*;
+    use crate::hal::host::{HostDisplay, ScriptedInput};
+
+    const BUTTON: InputEvent = InputEvent::Pressed(InputSource::Button);
+
+    fn key(key: usize) -> InputEvent {
+        InputEvent::Pressed(InputSource::Key(key))
+    }
+
+    /// Runs `entry` through `input`, which has to be used up.
+    fn enter<const N: usize>(
+        mut entry: TextEntry<'_, N>,
+        mut input: ScriptedInput,
+    ) -> Option<String<N>> {
+        let text = block_on(entry.run(&mut HostDisplay::default(), &mut input));
+        assert!(input.is_empty());
+
+        text
+    }
+
+    fn text<const N: usize>(text: &str) -> Option<String<N>> {
+        Some(String::try_from(text).unwrap())
+    }
+
+    #[test]
+    fn taps_cycle_through_the_key() {
+        let input = ScriptedInput::new([key(1), key(1), key(1), BUTTON, BUTTON]);
+        assert_eq!(enter(TextEntry::<8>::new("Name"), input), text("c"));
+
+        // Past the digit it starts over.
+        let input = ScriptedInput::new([key(1), key(1), key(1), key(1), key(1), BUTTON, BUTTON]);
+        assert_eq!(enter(TextEntry::<8>::new("Name"), input), text("a"));
+    }
+
+    #[test]
+    fn other_key_commits_the_pending_char() {
+        let input = ScriptedInput::new([key(1), key(2), key(2), BUTTON, BUTTON]);
+        assert_eq!(enter(TextEntry::<8>::new("Name"), input), text("ae"));
+    }
+
+    #[test]
+    fn pending_char_is_committed_after_the_timeout() {
+        let mut input = ScriptedInput::new([key(1)]);
+        input.stall().push(key(1)).push(BUTTON).push(BUTTON);
+        assert_eq!(enter(TextEntry::<8>::new("Name"), input), text("aa"));
+    }
+
+    #[test]
+    fn wheel_wraps_around() {
+        let input = ScriptedInput::new([
+            InputEvent::TurnedCCW(1),
+            InputEvent::TurnedCCW(1),
+            BUTTON,
+            InputEvent::TurnedCW(1),
+            InputEvent::TurnedCW(1),
+            BUTTON,
+            BUTTON,
+        ]);
+        assert_eq!(enter(TextEntry::<8>::new("Name"), input), text("%b"));
+    }
+
+    #[test]
+    fn shift_enters_uppercase() {
+        let input = ScriptedInput::new([key(KEY_SHIFT), key(1), BUTTON, BUTTON]);
+        assert_eq!(enter(TextEntry::<8>::new("Name"), input), text("A"));
+    }
+
+    #[test]
+    fn backspace_drops_the_pending_char_first() {
+        let input = ScriptedInput::new([key(1), key(KEY_BACKSPACE), BUTTON]);
+        let entry = TextEntry::<8>::new("Name").with_text("ab");
+        assert_eq!(enter(entry, input), text("ab"));
+
+        let input = ScriptedInput::new([key(KEY_BACKSPACE), BUTTON]);
+        let entry = TextEntry::<8>::new("Name").with_text("ab");
+        assert_eq!(enter(entry, input), text("a"));
+    }
+
+    #[test]
+    fn backspace_on_empty_text_cancels() {
+        let input = ScriptedInput::new([key(1), key(KEY_BACKSPACE), key(KEY_BACKSPACE)]);
+        assert_eq!(enter(TextEntry::<8>::new("Name"), input), None);
+    }
+
+    #[test]
+    fn full_text_takes_no_more_chars() {
+        let input = ScriptedInput::new([key(1), InputEvent::TurnedCW(1), BUTTON]);
+        let entry = TextEntry::<3>::new("Name").with_text("abc");
+        assert_eq!(enter(entry, input), text("abc"));
+    }
+
+    #[test]
+    fn initial_text_is_ascii_and_fits() {
+        let input = ScriptedInput::new([BUTTON]);
+        let entry = TextEntry::<4>::new("Name").with_text("Tétris");
+        assert_eq!(enter(entry, input), text("Ttri"));
+    }
+}
@@ -0,0 +1,203 @@
+//! The USB device: a CDC ACM serial port that streams the log while a terminal
+//! has it open, and takes commands to upload CHIP-8 ROMs into flash:
+//!
+//! ```text
+//! upload <name> <length>    followed by <length> bytes of ROM
+//! delete <name>
+//! list
+//! ```
+//!
+//! Replies go to the log, which is streamed back over the same port.
+
+use embassy_futures::join::join3;
+use embassy_rp::{peripherals::USB, usb::Driver};
+use embassy_usb::{
+    class::cdc_acm::{CdcAcmClass, Receiver, Sender, State},
+    driver::EndpointError,
+    Builder, Config,
+};
+use heapless::Vec;
+
+use crate::{
+    info, log,
+    rom_store::{self, HEADER_LEN, MAX_ROM_LEN, SLOT_SIZE},
+    status_bar, warn,
+};
+
+const MAX_PACKET_SIZE: u16 = 64;
+const MAX_COMMAND_LEN: usize = 64;
+
+/// Runs the USB device.
+pub async fn run(driver: Driver<'static, USB>) {
+    let mut config = Config::new(0x2e8a, 0x000a);
+    config.manufacturer = Some("macropad-apps");
+    config.product = Some("Macropad");
+    config.max_power = 100;
+    config.max_packet_size_0 = MAX_PACKET_SIZE as u8;
+
+    let mut config_descriptor = [0; 256];
+    let mut bos_descriptor = [0; 256];
+    let mut control_buf = [0; 64];
+    let mut state = State::new();
+
+    let mut builder = Builder::new(
+        driver,
+        config,
+        &mut config_descriptor,
+        &mut bos_descriptor,
+        &mut [],
+        &mut control_buf,
+    );
+    let class = CdcAcmClass::new(&mut builder, &mut state, MAX_PACKET_SIZE);
+    let mut usb = builder.build();
+    let (mut sender, mut receiver) = class.split();
+
+    let stream_log = async {
+        loop {
+            sender.wait_connection().await;
+            status_bar::set_usb_connected(true);
+            log::set_usb_stream_enabled(true);
+            info!("serial log connected");
+
+            let _ = forward_log(&mut sender).await;
+
+            log::set_usb_stream_enabled(false);
+            status_bar::set_usb_connected(false);
+        }
+    };
+
+    let commands = async {
+        loop {
+            receiver.wait_connection().await;
+            let _ = receive_commands(&mut receiver).await;
+        }
+    };
+
+    join3(usb.run(), stream_log, commands).await;
+}
+
+async fn forward_log(sender: &mut Sender<'_, Driver<'static, USB>>) -> Result<(), EndpointError> {
+    let mut packet = [0; MAX_PACKET_SIZE as usize];
+    loop {
+        let len = log::USB_STREAM.read(&mut packet).await;
+        sender.write_packet(&packet[..len]).await?;
+    }
+}
+
+enum Command<'a> {
+    Upload { name: &'a str, len: usize },
+    Delete(&'a str),
+    List,
+}
+
+impl<'a> Command<'a> {
+    fn parse(line: &'a str) -> Option<Self> {
+        let (command, arguments) = line.split_once(' ').unwrap_or((line, ""));
+        match command {
+            // The length comes last, so names may contain spaces.
+            "upload" => {
+                let (name, len) = arguments.rsplit_once(' ')?;
+                Some(Command::Upload {
+                    name: name.trim(),
+                    len: len.parse().ok()?,
+                })
+            }
+            "delete" => Some(Command::Delete(arguments.trim())),
+            "list" => Some(Command::List),
+            _ => None,
+        }
+    }
+}
+
+/// A ROM being received into slot `slot`.
+struct Upload {
+    slot: usize,
+    len: usize,
+    received: usize,
+}
+
+async fn receive_commands(
+    receiver: &mut Receiver<'_, Driver<'static, USB>>,
+) -> Result<(), EndpointError> {
+    let mut packet = [0; MAX_PACKET_SIZE as usize];
+    let mut line: Vec<u8, MAX_COMMAND_LEN> = Vec::new();
+    let mut sector = [0; SLOT_SIZE];
+    let mut upload: Option<Upload> = None;
+
+    loop {
+        let len = receiver.read_packet(&mut packet).await?;
+        for &byte in &packet[..len] {
+            if let Some(current) = &mut upload {
+                sector[HEADER_LEN + current.received] = byte;
+                current.received += 1;
+                if current.received == current.len {
+                    if rom_store::write(current.slot, &sector).is_ok() {
+                        info!("stored {} bytes in slot {}", current.len, current.slot);
+                    }
+                    upload = None;
+                }
+            } else if byte == b'\n' {
+                upload = run_command(&line, &mut sector);
+                line.clear();
+            } else if line.push(byte).is_err() {
+                warn!("command too long");
+                line.clear();
+            }
+        }
+    }
+}
+
+/// Runs the command in `line`. Returns the upload it starts, with the header
+/// already in `sector`.
+fn run_command(line: &[u8], sector: &mut [u8; SLOT_SIZE]) -> Option<Upload> {
+    let Some(command) = core::str::from_utf8(line)
+        .ok()
+        .and_then(|line| Command::parse(line.trim_end_matches('\r')))
+    else {
+        warn!("unknown command, expected upload, delete or list");
+        return None;
+    };
+
+    match command {
+        Command::Upload { name, len } => {
+            if len == 0 || len > MAX_ROM_LEN {
+                warn!("ROMs have to be 1 to {} bytes", MAX_ROM_LEN);
+                return None;
+            }
+            let Some(slot) = rom_store::slot_for(name) else {
+                warn!("no free slot, delete a ROM first");
+                return None;
+            };
+            sector.fill(0xff);
+            let header = sector.first_chunk_mut::<HEADER_LEN>()?;
+            if rom_store::encode_header(header, name, len).is_err() {
+                warn!("names have 1 to {} bytes", rom_store::NAME_LEN);
+                return None;
+            }
+
+            info!("receiving {} bytes for {}", len, name);
+            Some(Upload {
+                slot,
+                len,
+                received: 0,
+            })
+        }
+        Command::Delete(name) => {
+            match rom_store::list().iter().find(|rom| rom.name == name) {
+                Some(rom) => {
+                    if rom_store::erase(rom.slot).is_ok() {
+                        info!("deleted {}", name);
+                    }
+                }
+                None => warn!("no ROM named {}", name),
+            }
+            None
+        }
+        Command::List => {
+            for rom in rom_store::list() {
+                info!("{}: {} ({} bytes)", rom.slot, rom.name, rom.data.len());
+            }
+            None
+        }
+    }
+}
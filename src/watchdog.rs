@@ -0,0 +1,159 @@
+//! Task supervision on top of the RP2040 hardware watchdog. Long running tasks
+//! register a [`Watched`] and check in from their loop. The supervisor keeps
+//! feeding the hardware watchdog only while every task checked in within its
+//! timeout, and remembers which one stalled across the reset.
+
+use core::{
+    cell::{Cell, RefCell},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+#[cfg(feature = "rp2040")]
+use embassy_rp::watchdog::{ResetReason, Watchdog};
+use embassy_sync::blocking_mutex::{raw::CriticalSectionRawMutex, Mutex};
+#[cfg(feature = "rp2040")]
+use embassy_time::Timer;
+use embassy_time::{Duration, Instant};
+use heapless::Vec;
+
+use crate::error;
+
+pub const MAX_TASKS: usize = 8;
+
+pub struct Watched {
+    name: &'static str,
+    timeout: Duration,
+    last_check_in: Mutex<CriticalSectionRawMutex, Cell<Option<Instant>>>,
+    stall_requested: AtomicBool,
+}
+
+impl Watched {
+    pub const fn new(name: &'static str, timeout: Duration) -> Self {
+        Watched {
+            name,
+            timeout,
+            last_check_in: Mutex::new(Cell::new(None)),
+            stall_requested: AtomicBool::new(false),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn check_in(&self) {
+        if self.stall_requested.load(Ordering::Relaxed) {
+            error!("stalling {} on request", self.name);
+            // Never yield, like a task stuck in a busy loop would.
+            loop {
+                core::hint::spin_loop();
+            }
+        }
+
+        self.last_check_in
+            .lock(|last_check_in| last_check_in.set(Some(Instant::now())));
+    }
+
+    /// Stops supervising the task until its next check in, for tasks that are
+    /// about to wait for something indefinitely.
+    pub fn pause(&self) {
+        self.last_check_in
+            .lock(|last_check_in| last_check_in.set(None));
+    }
+
+    /// Time since the last check in, `None` before the first one.
+    pub fn since_check_in(&self) -> Option<Duration> {
+        self.last_check_in
+            .lock(|last_check_in| last_check_in.get())
+            .map(|instant| instant.elapsed())
+    }
+
+    pub fn is_stalled(&self) -> bool {
+        self.since_check_in()
+            .is_some_and(|elapsed| elapsed > self.timeout)
+    }
+
+    /// Makes the task hang on its next check in, to test the supervisor.
+    pub fn request_stall(&self) {
+        self.stall_requested.store(true, Ordering::Relaxed);
+    }
+}
+
+static TASKS: Mutex<CriticalSectionRawMutex, RefCell<Vec<&'static Watched, MAX_TASKS>>> =
+    Mutex::new(RefCell::new(Vec::new()));
+
+/// Adds `task` to the supervised tasks. Tasks have to be registered in the same
+/// order on every boot, the index identifies a stalled task after the reset.
+pub fn register(task: &'static Watched) {
+    TASKS.lock(|tasks| {
+        if tasks.borrow_mut().push(task).is_err() {
+            error!("too many watched tasks, not supervising {}", task.name);
+        }
+    });
+}
+
+pub fn tasks() -> Vec<&'static Watched, MAX_TASKS> {
+    TASKS.lock(|tasks| tasks.borrow().clone())
+}
+
+pub fn find_stalled() -> Option<(usize, &'static Watched)> {
+    tasks()
+        .into_iter()
+        .enumerate()
+        .find(|(_, task)| task.is_stalled())
+}
+
+/// Why the last reset happened, if it was the watchdog.
+pub enum ResetReport {
+    /// The registered task with this index stopped checking in.
+    TaskStalled(usize),
+    /// The supervisor itself did not get to feed the watchdog in time.
+    TimedOut,
+}
+
+#[cfg(feature = "rp2040")]
+const HARDWARE_TIMEOUT: Duration = Duration::from_secs(2);
+#[cfg(feature = "rp2040")]
+const FEED_INTERVAL: Duration = Duration::from_millis(500);
+
+#[cfg(feature = "rp2040")]
+const SCRATCH_STALL: usize = 0;
+#[cfg(feature = "rp2040")]
+const STALL_MAGIC: u32 = 0x5354_0000;
+
+/// Reads and clears the reason for the last reset. The scratch registers
+/// survive the watchdog reset.
+#[cfg(feature = "rp2040")]
+pub fn take_reset_report(watchdog: &mut Watchdog) -> Option<ResetReport> {
+    let scratch = watchdog.get_scratch(SCRATCH_STALL);
+    watchdog.set_scratch(SCRATCH_STALL, 0);
+
+    if scratch & 0xffff_0000 == STALL_MAGIC {
+        return Some(ResetReport::TaskStalled((scratch & 0xffff) as usize));
+    }
+
+    match watchdog.reset_reason() {
+        Some(ResetReason::TimedOut) => Some(ResetReport::TimedOut),
+        _ => None,
+    }
+}
+
+/// Feeds the hardware watchdog while all registered tasks are healthy. Should
+/// run on an interrupt executor so that it still gets to record a task that
+/// blocks the thread mode executor.
+#[cfg(feature = "rp2040")]
+pub async fn supervise(mut watchdog: Watchdog) -> ! {
+    watchdog.pause_on_debug(true);
+    watchdog.start(HARDWARE_TIMEOUT);
+
+    loop {
+        if let Some((index, task)) = find_stalled() {
+            error!("{} stopped responding, resetting", task.name());
+            watchdog.set_scratch(SCRATCH_STALL, STALL_MAGIC | index as u32);
+            watchdog.trigger_reset();
+        }
+
+        watchdog.feed();
+        Timer::after(FEED_INTERVAL).await;
+    }
+}